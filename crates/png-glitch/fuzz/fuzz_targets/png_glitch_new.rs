@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use png_glitch::{FilterType, PngGlitch};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(mut png_glitch) = PngGlitch::new(data.to_vec()) {
+        png_glitch.remove_filter();
+        png_glitch.apply_filter(FilterType::Paeth);
+        let mut buffer = vec![];
+        let _ = png_glitch.encode(&mut buffer);
+    }
+});
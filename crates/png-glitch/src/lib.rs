@@ -1,10 +1,14 @@
-use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Write};
 use std::path::Path;
 pub use crate::operation::Transpose;
-use crate::operation::{Encode, Scan};
+pub use crate::operation::{
+    AudioEffect, Axis, BitOperation, BitwiseOptions, BlockOperation, BlockOptions, ChannelOffset, ChannelShiftOptions, ConvertOptions,
+    DatabendOptions, Difference, DisplaceOptions, Displacement, Domain, EdgeMode, FilterStrategy, GradientDirection,
+    Length, LineDifference, Mask, PadOptions, RawLayout, Region, ResidualOperation, ResidualOptions, Rotation, SortDirection, SortKey, SortOptions,
+};
+use crate::operation::{Bitwise, ChannelShift, Convert, Databend, Diff, Displace, Encode, Filter, Geometry, MoveBlock, Raw, Residual, Scan, Sort};
 use crate::png::Png;
-pub use crate::png::{FilterType, ScanLine};
+pub use crate::png::{ColorType, Error, FilterType, OperationError, ParseOptions, Pixel, PngError, ScanLine, SidecarError, Snapshot, UnknownFilterPolicy};
 
 mod png;
 mod operation;
@@ -31,6 +35,9 @@ mod operation;
 /// png_glitch.save("./glitched.png").expect("The glitched file should be saved as a PNG file");
 /// ```
 ///
+/// Cloning a PngGlitch object copies the scan lines and the history, so that the clone is glitched independently.
+///
+#[derive(Clone)]
 pub struct PngGlitch {
     png: Png,
 }
@@ -48,14 +55,92 @@ impl PngGlitch {
     ///
     /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
     /// ```
-    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<PngGlitch> {
-        let mut file = File::open(path)?;
-        let mut buf = vec![];
-        file.read_to_end(&mut buf)?;
-        PngGlitch::new(buf)
+    pub fn open(path: impl AsRef<Path>) -> Result<PngGlitch, Error> {
+        PngGlitch::open_with_options(path, &ParseOptions::default())
+    }
+
+    /// The method creates a PngGlitch object from the PNG file at the given path, parsing it with the given options.
+    ///
+    /// # Example
+    ///
+    /// The decoded scan lines are limited to 1 GiB by default, to avoid huge allocation caused by a broken IHDR chunk.
+    /// Gigapixel scans exceed it. A 40000 x 30000 image in 8-bit RGB takes about 3.6 GB, for example.
+    /// Raise `max_decoded_size` to glitch them. The image keeps the limit, which also applies to the images created from it,
+    /// such as by `convert`, `crop`, `pad`, `resize`, `rotate`, `flip`, and the heatmap of `diff`.
+    ///
+    /// The following example allows the decoded scan lines to take up to 4 GiB.
+    ///
+    /// ```
+    /// use png_glitch::{ParseOptions, PngGlitch};
+    ///
+    /// let options = ParseOptions { max_decoded_size: 1 << 32, ..ParseOptions::default() };
+    /// let png_glitch = PngGlitch::open_with_options("./etc/sample00.png", &options).expect("The PNG file should be successfully parsed");
+    /// ```
+    pub fn open_with_options(path: impl AsRef<Path>, options: &ParseOptions) -> Result<PngGlitch, Error> {
+        let png = Png::open(path, options)?;
+        Ok(PngGlitch { png })
+    }
+
+    /// The method creates a PngGlitch object to glitch the PNG image read from the given reader.
+    /// Chunks are read one by one, and IDAT chunks are inflated as soon as they are read.
+    /// Only the decoded scan lines are kept in memory, which helps to glitch large images.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    /// use png_glitch::PngGlitch;
+    ///
+    /// let file = File::open("./etc/sample00.png").expect("The file should be opened");
+    /// let mut png_glitch = PngGlitch::read(BufReader::new(file)).expect("The PNG file should be successfully parsed");
+    /// ```
+    pub fn read(reader: impl Read) -> Result<PngGlitch, Error> {
+        PngGlitch::read_with_options(reader, &ParseOptions::default())
+    }
+
+    /// The method creates a PngGlitch object from the PNG image read from the given reader, parsing it with the given options.
+    pub fn read_with_options(reader: impl Read, options: &ParseOptions) -> Result<PngGlitch, Error> {
+        let png = Png::read(reader, options)?;
+        Ok(PngGlitch { png })
+    }
+
+    /// The method creates a PngGlitch object from the memory mapped PNG file at the given path.
+    /// Chunks are parsed without copying their payload: IDAT chunks are inflated directly from the mapped memory,
+    /// and the other chunks keep referring to it until they are written. This method requires `mmap` feature.
+    /// The mapping is kept until the object and its forks are dropped, though only the pages read on parsing and encoding are loaded.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the returned object is alive,
+    /// since the chunks other than IDAT are read from the mapped memory on encoding.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use png_glitch::PngGlitch;
+    ///
+    /// let png_glitch = unsafe { PngGlitch::open_mmap("./etc/sample00.png") }.expect("The PNG file should be successfully parsed");
+    /// ```
+    #[cfg(feature = "mmap")]
+    pub unsafe fn open_mmap(path: impl AsRef<Path>) -> Result<PngGlitch, Error> {
+        PngGlitch::open_mmap_with_options(path, &ParseOptions::default())
+    }
+
+    /// The method creates a PngGlitch object from the memory mapped PNG file, parsing it with the given options.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the returned object is alive, as [PngGlitch::open_mmap].
+    #[cfg(feature = "mmap")]
+    pub unsafe fn open_mmap_with_options(path: impl AsRef<Path>, options: &ParseOptions) -> Result<PngGlitch, Error> {
+        let png = Png::open_mmap(path, options)?;
+        Ok(PngGlitch { png })
     }
 
     /// The method creates a PngGlitch object to glitch the PNG image stored in a given `Vec<u8>`.
+    /// IDAT chunks are inflated directly from the buffer, and only the payload of the other chunks is copied,
+    /// so that the buffer is released once the image is parsed. Only the decoded scan lines are kept in memory with those chunks.
     ///
     /// # Example
     ///
@@ -71,11 +156,50 @@ impl PngGlitch {
     /// file.read_to_end(&mut buffer).expect("The bytes in the file should be written into the buffer");
     /// let mut png_glitch = PngGlitch::new(buffer).expect("The data in the buffer should be successfully parsed as PNG");
     /// ```
-    pub fn new(buffer: Vec<u8>) -> anyhow::Result<PngGlitch> {
-        let png = Png::try_from(&buffer)?;
+    ///
+    /// # Errors
+    ///
+    /// [Error::Parse] is returned when the data is not a valid PNG image.
+    /// It tells the reason, the byte offset of the chunk where the error is found, and the type of the chunk.
+    ///
+    /// ```
+    /// use png_glitch::{Error, PngError, PngGlitch};
+    ///
+    /// match PngGlitch::new(b"GIF89a".to_vec()) {
+    ///     Err(Error::Parse { error: PngError::InvalidSignature, offset, .. }) => assert_eq!(0, offset),
+    ///     _ => panic!("The data should be rejected as it is not a PNG image"),
+    /// }
+    /// ```
+    pub fn new(buffer: Vec<u8>) -> Result<PngGlitch, Error> {
+        PngGlitch::new_with_options(buffer, &ParseOptions::default())
+    }
+
+    /// The method creates a PngGlitch object from the PNG image stored in the given buffer, parsing it with the given options.
+    pub fn new_with_options(buffer: Vec<u8>, options: &ParseOptions) -> Result<PngGlitch, Error> {
+        let png = Png::parse_buffer(buffer, options)?;
         Ok(PngGlitch { png })
     }
 
+    /// The method creates an independent copy of the PngGlitch object without the history.
+    /// It is cheaper than parsing the same image again, since the scan lines are copied without inflating IDAT chunks.
+    ///
+    /// # Example
+    ///
+    /// The following example generates variants of the image, each of which has a different filter type.
+    ///
+    /// ```
+    /// use png_glitch::{FilterType, PngGlitch};
+    /// let png_glitch = PngGlitch::open("./etc/none.png").expect("The PNG file should be successfully parsed");
+    /// for (index, filter_type) in [FilterType::Sub, FilterType::Up, FilterType::Average].into_iter().enumerate() {
+    ///     let mut variant = png_glitch.fork();
+    ///     variant.apply_filter(filter_type);
+    ///     variant.save(std::env::temp_dir().join(format!("variant-{}.png", index))).expect("The PNG file should be successfully saved");
+    /// }
+    /// ```
+    pub fn fork(&self) -> PngGlitch {
+        PngGlitch { png: self.png.fork() }
+    }
+
     /// The method returns a list of [scan line](https://www.w3.org/TR/2003/REC-PNG-20031110/#4Concepts.EncodingScanlineAbs%22). in the given PNG file.
     ///
     /// # Example
@@ -153,7 +277,7 @@ impl PngGlitch {
     /// let png_glitch = PngGlitch::open("etc/sample00.png").expect("The PNG file should be successfully parsed");
     /// png_glitch.save("./glitched.png").expect("The glitched PNG data should be saved to the given path");
     /// ```
-    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
         self.png.save(path)
     }
 
@@ -172,8 +296,27 @@ impl PngGlitch {
     /// let mut encoded_data:Vec<u8> = vec![];
     /// png_glitch.encode(&mut encoded_data).expect("The glitched PNG data should be written into the encoded_data in PNG format");
     /// ```
-    pub fn encode(&self, buffer: &mut Vec<u8>) -> anyhow::Result<()> {
-        let _ = self.png.encode(buffer)?;
+    pub fn encode(&self, buffer: &mut Vec<u8>) -> Result<(), Error> {
+        self.png.encode(buffer)?;
+        Ok(())
+    }
+
+    /// The method encodes the glitched image as a PNG data and writes it to the given writer.
+    /// Scan lines are compressed in bands, and each band is written as IDAT chunks as soon as it is compressed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::BufWriter;
+    /// use png_glitch::PngGlitch;
+    ///
+    /// let png_glitch = PngGlitch::open("./etc/none.png").expect("The PNG file should be successfully parsed");
+    /// let file = File::create(std::env::temp_dir().join("write.png")).expect("The file should be created");
+    /// png_glitch.write(BufWriter::new(file)).expect("The glitched PNG data should be written to the file");
+    /// ```
+    pub fn write(&self, writer: impl Write) -> Result<(), Error> {
+        self.png.encode(writer)?;
         Ok(())
     }
 
@@ -213,6 +356,134 @@ impl PngGlitch {
         self.png.height()
     }
 
+    /// The method returns the color type of the image.
+    pub fn color_type(&self) -> ColorType {
+        self.png.color_type()
+    }
+
+    /// The method returns the bit depth of the image.
+    pub fn bit_depth(&self) -> u8 {
+        self.png.bit_depth()
+    }
+
+    /// The method converts the image into the color type and the bit depth specified in the options.
+    /// The IHDR chunk is rewritten and the scan lines are re-sliced, while each scan line keeps its filter type.
+    /// Since filters work on bytes, the same glitch looks very different in another color type or bit depth.
+    ///
+    /// Colors are converted as follows:
+    ///
+    /// * Grayscale is the brightness of the color.
+    /// * Alpha is dropped, or added as opaque.
+    /// * Index color images get a palette of `options.colors` colors at most, quantized with the median cut algorithm.
+    ///
+    /// Chunks depending on the color type, such as PLTE, tRNS and bKGD, are dropped on conversion.
+    /// The history is cleared, since its steps can not be applied to the scan lines in another layout.
+    /// An error is returned if the bit depth is not allowed for the color type.
+    ///
+    /// # Example
+    ///
+    /// The following example reduces the colors into a palette of 16 colors, and then filters the scan lines.
+    ///
+    /// ```
+    /// use png_glitch::{ColorType, ConvertOptions, FilterType, PngGlitch};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.convert(&ConvertOptions {
+    ///     colors: 16,
+    ///     ..ConvertOptions::new(ColorType::IndexColor, 4)
+    /// }).expect("The bit depth should be allowed for the color type");
+    /// png_glitch.apply_filter(FilterType::Paeth);
+    /// png_glitch.save(std::env::temp_dir().join("converted.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn convert(&mut self, options: &ConvertOptions) -> Result<(), Error> {
+        self.record(|png| png.convert(options))
+    }
+
+    /// The method crops the image to the region. The region is clipped to the image.
+    /// Each scan line keeps its filter type, and the history is cleared as the size changes.
+    /// [OperationError::RegionOutOfBounds] is returned if the region does not overlap the image.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use png_glitch::{PngGlitch, Region};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.crop(&Region::new(100, 100, 200, 150)).expect("The region should overlap the image");
+    /// png_glitch.save(std::env::temp_dir().join("cropped.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn crop(&mut self, region: &Region) -> Result<(), Error> {
+        self.record(|png| png.crop(region))
+    }
+
+    /// The method adds margins around the image, filled with the pixel in the options.
+    /// Scan lines in the margins take the filter type of the nearest scan line of the original image.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use png_glitch::{PadOptions, Pixel, PngGlitch};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.pad(&PadOptions {
+    ///     pixel: Pixel::new(&[255, 255, 255]),
+    ///     ..PadOptions::new(20)
+    /// }).expect("The padded image should not be too large");
+    /// png_glitch.save(std::env::temp_dir().join("padded.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn pad(&mut self, options: &PadOptions) -> Result<(), Error> {
+        self.record(|png| png.pad(options))
+    }
+
+    /// The method resizes the image with the nearest neighbour interpolation, which keeps the colors of the pixels as they are.
+    /// Each scan line takes the filter type of the scan line of the original image it is scaled from.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use png_glitch::PngGlitch;
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.resize(png_glitch.width() / 4, png_glitch.height() / 4).expect("The size should not be zero");
+    /// png_glitch.save(std::env::temp_dir().join("resized.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        self.record(|png| png.resize(width, height))
+    }
+
+    /// The method rotates the image by 90, 180 or 270 degrees.
+    /// A quarter turn makes the scan lines from the columns, which have no original scan line to take the filter type from,
+    /// so they are left unfiltered with the filter type None. A half turn keeps the filter type of each scan line.
+    ///
+    /// # Example
+    ///
+    /// Glitches on scan lines look like horizontal streaks.
+    /// The following example rotates the image before glitching and rotates it back, so that the streaks become vertical.
+    ///
+    /// ```
+    /// use png_glitch::{BitOperation, BitwiseOptions, PngGlitch, Rotation};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.rotate(Rotation::Clockwise).expect("The rotated image should not be too large");
+    /// png_glitch.bitwise(&BitwiseOptions::new(BitOperation::Flip { probability: 0.001, seed: 42 }));
+    /// png_glitch.rotate(Rotation::CounterClockwise).expect("The rotated image should not be too large");
+    /// png_glitch.save(std::env::temp_dir().join("vertical-streaks.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn rotate(&mut self, rotation: Rotation) -> Result<(), Error> {
+        self.record(|png| png.rotate(rotation))
+    }
+
+    /// The method flips the image horizontally or vertically.
+    /// Flipping vertically moves the filter types along with the scan lines.
+    /// An error is returned if the flipped image is too large to create.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use png_glitch::{Axis, PngGlitch};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.flip(Axis::Horizontal).expect("The image should be flipped");
+    /// png_glitch.save(std::env::temp_dir().join("flipped.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn flip(&mut self, axis: Axis) -> Result<(), Error> {
+        self.record(|png| png.flip(axis))
+    }
+
     /// The method copies the lines starting from src to dest
     ///
     /// # Example
@@ -223,7 +494,26 @@ impl PngGlitch {
     /// let width = png_glitch.transpose(2, 5, 10);
     /// ```
     pub fn transpose(&mut self, src: u32, dst: u32, lines: u32) {
-        self.png.transpose(src as usize, dst as usize, lines)
+        let from = src.min(dst) as usize;
+        let span = (src.max(dst) as usize - from).saturating_add(lines as usize);
+        self.record_lines(from, span, |png| png.transpose(src as usize, dst as usize, lines))
+    }
+
+    /// The method specifies how scan lines with an unknown filter type are handled on removing filters.
+    /// Scan lines with an unknown filter type are treated as if no filter is applied by default.
+    /// The policy is initialized with `ParseOptions::unknown_filter_policy` on parsing.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use png_glitch::{PngGlitch, UnknownFilterPolicy};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.foreach_scanline(|scan_line| scan_line.set_filter_type(7.into()));
+    /// png_glitch.set_unknown_filter_policy(UnknownFilterPolicy::Modulo);
+    /// png_glitch.remove_filter(); // Each scan line is handled as if Up filter is applied
+    /// ```
+    pub fn set_unknown_filter_policy(&mut self, policy: UnknownFilterPolicy) {
+        self.png.set_unknown_filter_policy(policy);
     }
 
     /// The method removes filter from all scan lines.
@@ -234,7 +524,7 @@ impl PngGlitch {
     /// use png_glitch::PngGlitch;
     /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
     /// png_glitch.remove_filter();
-    /// png_glitch.save("./etc/removed-all.png").expect("The PNG file should be successfully saved")
+    /// png_glitch.save(std::env::temp_dir().join("removed-all.png")).expect("The PNG file should be successfully saved")
     /// ```
     pub fn remove_filter(&mut self) {
         self.remove_filter_from(0, self.height());
@@ -248,26 +538,14 @@ impl PngGlitch {
     /// use png_glitch::PngGlitch;
     /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
     /// png_glitch.remove_filter_from(5, 10); // Remove filter from the scan line #5 - # 14
-    /// png_glitch.save("./etc/removed-partial.png").expect("The PNG file should be successfully saved")
+    /// png_glitch.save(std::env::temp_dir().join("removed-partial.png")).expect("The PNG file should be successfully saved")
     /// ```
     pub fn remove_filter_from(&mut self, from: u32, lines: u32) {
-        let index = if from > 0 { from - 1 } else { 0 };
-        let mut lines = self.scan_lines_from(index, lines);
-        lines.reverse();
-
-        let mut previous = if from > 0 {
-            lines.pop()
-        } else {
-            None
-        };
-        while lines.len() > 0 {
-            let last_index = lines.len() - 1;
-            lines[last_index].remove_filter(previous.as_ref());
-            previous = lines.pop()
-        }
+        self.record_lines(from as usize, lines as usize, |png| png.remove_filter_from(from as usize, lines as usize))
     }
 
-    /// The method removes filter from all scan lines.
+    /// The method applies the filter to all scan lines.
+    /// The bytes of each scan line are treated as unfiltered ones, so that `remove_filter` restores them.
     ///
     /// # Example
     ///
@@ -275,13 +553,14 @@ impl PngGlitch {
     /// use png_glitch::{FilterType, PngGlitch};
     /// let mut png_glitch = PngGlitch::open("./etc/none.png").expect("The PNG file should be successfully parsed");
     /// png_glitch.apply_filter(FilterType::Sub);
-    /// png_glitch.save("./etc/filter-all.png").expect("The PNG file should be successfully saved")
+    /// png_glitch.save(std::env::temp_dir().join("filter-all.png")).expect("The PNG file should be successfully saved")
     /// ```
     pub fn apply_filter(&mut self, filter: FilterType) {
         self.apply_filter_from(filter, 0, self.height());
     }
 
-    /// The method removes filter from scan lines in specified region
+    /// The method applies the filter to the scan lines in specified region.
+    /// The scan line just before the region is treated as an unfiltered one.
     ///
     /// # Example
     ///
@@ -289,18 +568,407 @@ impl PngGlitch {
     /// use png_glitch::{FilterType, PngGlitch};
     /// let mut png_glitch = PngGlitch::open("./etc/none.png").expect("The PNG file should be successfully parsed");
     /// png_glitch.apply_filter_from(FilterType::Sub, 5, 3); // Apply sub filter to the scan line #5, #6, and #7.
-    /// png_glitch.save("./etc/filter-partial.png").expect("The PNG file should be successfully saved")
+    /// png_glitch.save(std::env::temp_dir().join("filter-partial.png")).expect("The PNG file should be successfully saved")
     /// ```
     pub fn apply_filter_from(&mut self, filter_type: FilterType, from: u32, lines: u32) {
-        let mut lines = self.scan_lines_from(from, lines);
-        let mut previous = lines.pop();
+        self.record_lines(from as usize, lines as usize, |png| png.apply_filter_from(filter_type, from as usize, lines as usize))
+    }
 
-        while lines.len() > 0 {
-            if let Some(mut line) = previous {
-                previous = lines.pop();
-                line.apply_filter(filter_type, previous.as_ref());
-            }
-        }
+    /// The method applies the filter types in the plan to the scan lines starting from `from`, one filter type per scan line.
+    /// The bytes of the scan lines and the one just before them are treated as unfiltered ones,
+    /// so that `remove_filter_from(from, plan.len())` restores them exactly.
+    /// Filter types beyond the last scan line are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use png_glitch::{FilterType, PngGlitch};
+    /// let mut png_glitch = PngGlitch::open("./etc/none.png").expect("The PNG file should be successfully parsed");
+    /// let plan = [FilterType::Sub, FilterType::Up, FilterType::Average, FilterType::Paeth];
+    /// let plan: Vec<FilterType> = plan.into_iter().cycle().take(png_glitch.height() as usize).collect();
+    /// png_glitch.apply_filter_plan(&plan, 0);
+    /// png_glitch.save(std::env::temp_dir().join("filter-plan.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn apply_filter_plan(&mut self, plan: &[FilterType], from: u32) {
+        self.record_lines(from as usize, plan.len(), |png| png.apply_filter_plan(plan, from as usize))
     }
 
-}
\ No newline at end of file
+    /// The method filters the scan lines in specified region with the filter types chosen by the strategy,
+    /// replacing their current filters. The other scan lines are left as they are. The chosen filter types are returned.
+    ///
+    /// # Example
+    ///
+    /// The following example filters all scan lines with the filter types chosen by libpng's heuristic.
+    ///
+    /// ```
+    /// use png_glitch::{FilterStrategy, PngGlitch};
+    /// let mut png_glitch = PngGlitch::open("./etc/none.png").expect("The PNG file should be successfully parsed");
+    /// let filter_types = png_glitch.apply_filter_strategy(&FilterStrategy::MinimumSumOfAbsoluteDifferences, 0, png_glitch.height());
+    /// png_glitch.save(std::env::temp_dir().join("filter-strategy.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn apply_filter_strategy(&mut self, strategy: &FilterStrategy, from: u32, lines: u32) -> Vec<FilterType> {
+        self.record_lines(from as usize, lines as usize, |png| {
+            png.apply_filter_strategy(strategy, from as usize, lines as usize)
+        })
+    }
+
+    /// The method sorts pixels in each row or column of the image, known as pixel sorting.
+    /// Pixels are sorted without filters, and then each scan line is filtered with the filter type specified in the options,
+    /// or with its own filter type if none is specified.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use png_glitch::{FilterType, PngGlitch, SortDirection, SortKey, SortOptions};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.sort(&SortOptions {
+    ///     direction: SortDirection::Column,
+    ///     sort_key: SortKey::Hue,
+    ///     filter_type: Some(FilterType::Paeth),
+    ///     ..SortOptions::default()
+    /// });
+    /// png_glitch.save(std::env::temp_dir().join("sorted.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn sort(&mut self, options: &SortOptions) {
+        self.record(|png| png.sort(options))
+    }
+
+    /// The method moves the samples of each channel by the offsets specified in the options, known as RGB split.
+    /// Samples are shifted without filters, and then the shifted scan lines are filtered with the filter type specified in the options,
+    /// or with their own filter types if none is specified. The other scan lines are left as they were.
+    ///
+    /// # Example
+    ///
+    /// The following example moves red samples 8 pixels to the left and blue samples 8 pixels to the right in the scan line #100 - #199.
+    ///
+    /// ```
+    /// use png_glitch::{ChannelOffset, ChannelShiftOptions, EdgeMode, PngGlitch};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.shift_channels(&ChannelShiftOptions {
+    ///     edge_mode: EdgeMode::Clamp,
+    ///     from: 100,
+    ///     lines: 100,
+    ///     ..ChannelShiftOptions::new(vec![ChannelOffset::new(0, -8, 0), ChannelOffset::new(2, 8, 0)])
+    /// });
+    /// png_glitch.save(std::env::temp_dir().join("channel-shifted.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn shift_channels(&mut self, options: &ChannelShiftOptions) {
+        self.record_lines(options.from as usize, options.lines as usize, |png| png.shift_channels(options))
+    }
+
+    /// The method shifts each scan line horizontally in whole pixels by the amount specified in the options.
+    /// Shifting filtered bytes and unfiltered pixels give quite different results.
+    ///
+    /// # Example
+    ///
+    /// The following example shifts the scan lines along a sine wave, whose amplitude is 20 pixels and period is 50 lines.
+    ///
+    /// ```
+    /// use png_glitch::{Displacement, DisplaceOptions, Domain, PngGlitch};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.displace(&DisplaceOptions {
+    ///     domain: Domain::Unfiltered,
+    ///     ..DisplaceOptions::new(Displacement::Sine { amplitude: 20.0, period: 50.0, phase: 0.0 })
+    /// });
+    /// png_glitch.save(std::env::temp_dir().join("displaced.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn displace(&mut self, options: &DisplaceOptions) {
+        self.record_lines(options.from as usize, options.lines as usize, |png| png.displace(options))
+    }
+
+    /// The method copies, swaps, repeats or smears a rectangular block of pixels.
+    /// Working on filtered bytes makes the filters propagate the change to the following pixels.
+    ///
+    /// # Example
+    ///
+    /// The following example drags the 32x32 block at (100, 100) to (160, 140), leaving its copies on the way.
+    ///
+    /// ```
+    /// use png_glitch::{BlockOperation, BlockOptions, PngGlitch, Region};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.move_block(&BlockOptions::new(BlockOperation::Smear, Region::new(100, 100, 32, 32), 160, 140));
+    /// png_glitch.save(std::env::temp_dir().join("smeared.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn move_block(&mut self, options: &BlockOptions) {
+        self.record(|png| png.move_block(options))
+    }
+
+    /// The method modifies each byte of the scan lines with the bit operation specified in the options.
+    ///
+    /// # Example
+    ///
+    /// The following example flips 0.1% of bits in the scan line #100 - #149.
+    ///
+    /// ```
+    /// use png_glitch::{BitOperation, BitwiseOptions, PngGlitch};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.bitwise(&BitwiseOptions {
+    ///     from: 100,
+    ///     lines: 50,
+    ///     ..BitwiseOptions::new(BitOperation::Flip { probability: 0.001, seed: 42 })
+    /// });
+    /// png_glitch.save(std::env::temp_dir().join("bit-flipped.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn bitwise(&mut self, options: &BitwiseOptions) {
+        self.record_lines(options.from as usize, options.lines as usize, |png| png.bitwise(options))
+    }
+
+    /// The method processes the bytes of the scan lines as an audio signal with the effect specified in the options,
+    /// as if the image were opened in an audio editor. Each byte is an unsigned 8-bit sample.
+    ///
+    /// # Example
+    ///
+    /// The following example echoes the scan line #100 - #299 with the delay of 40 pixels.
+    ///
+    /// ```
+    /// use png_glitch::{AudioEffect, DatabendOptions, Length, PngGlitch};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.databend(&DatabendOptions {
+    ///     from: 100,
+    ///     lines: 200,
+    ///     ..DatabendOptions::new(AudioEffect::Echo { delay: Length::Pixels(40), feedback: 0.6, mix: 0.5 })
+    /// });
+    /// png_glitch.save(std::env::temp_dir().join("echoed.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn databend(&mut self, options: &DatabendOptions) {
+        self.record_lines(options.from as usize, options.lines as usize, |png| png.databend(options))
+    }
+
+    /// The method filters the scan lines with the filter type specified in the options, and then edits the filtered bytes.
+    /// The filter propagates the edits to the following pixels, so that the result is predictable:
+    /// edits on Sub filtered bytes make streaks along rows, and the ones on Up filtered bytes make streaks along columns.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use png_glitch::{FilterType, PngGlitch, ResidualOperation, ResidualOptions};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.edit_residuals(&ResidualOptions {
+    ///     from: 200,
+    ///     lines: 100,
+    ///     ..ResidualOptions::new(FilterType::Up, ResidualOperation::Quantize(16))
+    /// });
+    /// png_glitch.save(std::env::temp_dir().join("residuals-quantized.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn edit_residuals(&mut self, options: &ResidualOptions) {
+        self.record_lines(options.from as usize, options.lines as usize, |png| png.edit_residuals(options))
+    }
+
+    /// The method runs the given operation, and then restores the pixels not selected by the mask.
+    /// Any operation on PngGlitch can be restricted to the shape of the mask in this way.
+    /// Scan lines without any selected pixel keep their filter type, while the other ones take the filter type set by the operation.
+    ///
+    /// # Example
+    ///
+    /// The following example sorts pixels only in the ellipse at the center of the image.
+    ///
+    /// ```
+    /// use png_glitch::{Mask, PngGlitch, Region, SortOptions};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// let region = Region::new(150, 100, 300, 250);
+    /// let mask = Mask::ellipse(png_glitch.width(), png_glitch.height(), region);
+    /// png_glitch.with_mask(&mask, |png_glitch| png_glitch.sort(&SortOptions::default()));
+    /// png_glitch.save(std::env::temp_dir().join("masked.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn with_mask<F>(&mut self, mask: &Mask, operation: F)
+    where
+        F: FnOnce(&mut PngGlitch),
+    {
+        self.png.begin(0, usize::MAX);
+        let history = self.png.take_history();
+        let snapshot = self.png.snapshot();
+        operation(self);
+        self.png.restore_unmasked(mask, snapshot);
+        self.png.resume_history(history);
+        self.png.commit();
+    }
+
+    /// The method compares the scan lines with the ones of the other image, both before and after removing filters.
+    /// None is returned if the images differ in their size, color type, or bit depth.
+    ///
+    /// # Example
+    ///
+    /// The following example shows how an edit on a single byte propagates through Paeth filter.
+    ///
+    /// ```
+    /// use png_glitch::{FilterType, PngGlitch};
+    /// let mut original = PngGlitch::open("./etc/none.png").expect("The PNG file should be successfully parsed");
+    /// original.apply_filter(FilterType::Paeth);
+    /// let glitched = original.fork();
+    /// glitched.scan_lines()[10].update(100, 0);
+    ///
+    /// let difference = original.diff(&glitched).expect("The images should have the same size");
+    /// for line in difference.lines() {
+    ///     println!("#{}: filtered {:?}, unfiltered {:?}", line.index, line.filtered, line.unfiltered);
+    /// }
+    /// difference.heatmap().expect("The heatmap should be created").save(std::env::temp_dir().join("heatmap.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn diff(&self, other: &PngGlitch) -> Option<Difference> {
+        self.png.diff(&other.png)
+    }
+
+    /// The method writes the scan lines without their filter type bytes to a headerless raw file,
+    /// so that they can be edited in external tools such as audio editors or hex editors.
+    /// The filtered bytes are written with `Domain::Filtered`, and the unfiltered pixels with `Domain::Unfiltered`.
+    /// The layout of the raw file is written to the sidecar file, whose path is the given path with `.toml` appended.
+    /// It describes the width, height, color type, bit depth, and the filter type of each scan line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use png_glitch::{Domain, PngGlitch};
+    /// let png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.export_raw(std::env::temp_dir().join("sample00.raw"), Domain::Filtered).expect("The raw file should be successfully written");
+    /// ```
+    pub fn export_raw(&self, path: impl AsRef<Path>, domain: Domain) -> Result<(), Error> {
+        self.png.export_raw(path, domain)
+    }
+
+    /// The method reads the raw file written by `export_raw` back into the scan lines, following its sidecar file.
+    /// The filter types in the sidecar file are restored, or applied to the pixels if the raw file holds unfiltered pixels.
+    /// Bytes beyond the image are ignored, and the bytes of the scan lines past the end of the raw file are kept.
+    /// [Error::Sidecar] is returned if the sidecar file can not be read as a layout, and [Error::Operation] with
+    /// [OperationError::LayoutMismatch] or [OperationError::FilterCountMismatch] is returned if it does not match the image.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use png_glitch::{Domain, PngGlitch};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.export_raw(std::env::temp_dir().join("sample00-pixels.raw"), Domain::Unfiltered).expect("The raw file should be successfully written");
+    /// // Edit sample00-pixels.raw in the temp dir with an external tool
+    /// png_glitch.import_raw(std::env::temp_dir().join("sample00-pixels.raw")).expect("The raw file should be successfully read");
+    /// png_glitch.save(std::env::temp_dir().join("imported.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn import_raw(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        self.record(|png| png.import_raw(path))
+    }
+
+    /// The method copies the current scan lines into a Snapshot object, which can be restored later.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use png_glitch::{PngGlitch, SortOptions};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// let snapshot = png_glitch.snapshot();
+    /// png_glitch.sort(&SortOptions::default());
+    /// png_glitch.restore(&snapshot);
+    /// assert_eq!(snapshot, png_glitch.snapshot());
+    /// ```
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::new(self.png.snapshot())
+    }
+
+    /// The method replaces the scan lines with the ones in the snapshot.
+    /// Snapshots taken from an image in another size are ignored.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.record(|png| png.restore(snapshot))
+    }
+
+    /// The method starts recording the changes on the scan lines, so that they can be undone and redone.
+    /// Each operation on PngGlitch is recorded as a step, and `limit` steps are kept at most.
+    /// Steps keep only the changed scan lines. An operation copies the scan lines it may change while it runs,
+    /// and no copy of the image is kept between operations.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use png_glitch::{BitOperation, BitwiseOptions, FilterType, PngGlitch};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.enable_history(100);
+    /// png_glitch.apply_filter(FilterType::Sub);
+    /// png_glitch.bitwise(&BitwiseOptions::new(BitOperation::Xor(0x0F)));
+    /// png_glitch.undo(); // The bit operation is reverted
+    /// png_glitch.save(std::env::temp_dir().join("undone.png")).expect("The PNG file should be successfully saved")
+    /// ```
+    pub fn enable_history(&mut self, limit: usize) {
+        self.png.enable_history(limit)
+    }
+
+    /// The method starts a step recording the changes on the scan lines in specified region,
+    /// made with `foreach_scanline` or `scan_lines`. Only the scan lines in the region are copied.
+    /// The step ends at `commit`, or at the next operation, undo, or redo.
+    /// Changes made outside of a step are not recorded, and undoing a step overwrites them if they are on its scan lines.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use png_glitch::{FilterType, PngGlitch};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.enable_history(100);
+    /// png_glitch.begin(0, 10);
+    /// png_glitch.scan_lines_from(0, 10).iter_mut().for_each(|scan_line| scan_line.set_filter_type(FilterType::Up));
+    /// assert!(png_glitch.commit());
+    /// ```
+    pub fn begin(&mut self, from: u32, lines: u32) {
+        self.png.begin(from as usize, lines as usize)
+    }
+
+    /// The method ends the step started with `begin`, and records it.
+    /// It returns false if nothing is changed in the step, no step is started, or the history is not enabled.
+    pub fn commit(&mut self) -> bool {
+        self.png.commit()
+    }
+
+    /// The method reverts the last step. It returns false if there is no step to undo.
+    pub fn undo(&mut self) -> bool {
+        self.png.undo()
+    }
+
+    /// The method reapplies the last undone step. It returns false if there is no step to redo.
+    /// Any change after undoing discards the steps to redo.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use png_glitch::{FilterType, PngGlitch};
+    /// let mut png_glitch = PngGlitch::open("./etc/sample00.png").expect("The PNG file should be successfully parsed");
+    /// png_glitch.enable_history(100);
+    /// png_glitch.apply_filter(FilterType::Paeth);
+    /// let filtered = png_glitch.snapshot();
+    /// png_glitch.undo();
+    /// png_glitch.redo();
+    /// assert_eq!(filtered, png_glitch.snapshot());
+    /// ```
+    pub fn redo(&mut self) -> bool {
+        self.png.redo()
+    }
+
+    /// The method returns true if there is a step to undo.
+    pub fn can_undo(&self) -> bool {
+        self.png.can_undo()
+    }
+
+    /// The method returns true if there is a step to redo.
+    pub fn can_redo(&self) -> bool {
+        self.png.can_redo()
+    }
+
+    /// Runs the operation as a step of the history, which may change any scan line.
+    fn record<F, T>(&mut self, operation: F) -> T
+    where
+        F: FnOnce(&mut Png) -> T,
+    {
+        self.record_lines(0, usize::MAX, operation)
+    }
+
+    /// Runs the operation as a step of the history, which changes only `lines` scan lines from `from`.
+    /// Only those scan lines are copied to find the changes.
+    fn record_lines<F, T>(&mut self, from: usize, lines: usize, operation: F) -> T
+    where
+        F: FnOnce(&mut Png) -> T,
+    {
+        self.png.begin(from, lines);
+        let result = operation(&mut self.png);
+        self.png.commit();
+        result
+    }
+}
+
+impl From<PngGlitch> for Mask {
+    /// The weight of each pixel in the mask is the brightness of the pixel multiplied by its opacity.
+    fn from(mut png_glitch: PngGlitch) -> Self {
+        png_glitch.png.luminance_mask()
+    }
+}
@@ -1,7 +1,42 @@
 mod transpose;
 mod scan;
 mod encode;
+mod filter;
+mod filter_strategy;
+mod sort;
+mod edge_mode;
+mod channel_shift;
+mod domain;
+mod displace;
+mod region;
+mod block;
+mod mask;
+mod bitwise;
+mod residual;
+mod diff;
+mod databend;
+mod raw;
+mod convert;
+mod geometry;
 
 pub use transpose::Transpose;
 pub use encode::Encode;
-pub use scan::Scan;
\ No newline at end of file
+pub use scan::Scan;
+pub use filter::Filter;
+pub use filter_strategy::FilterStrategy;
+pub use edge_mode::EdgeMode;
+pub use domain::Domain;
+pub use displace::{Displace, DisplaceOptions, Displacement};
+pub use region::Region;
+pub use block::{BlockOperation, BlockOptions, MoveBlock};
+pub use mask::{GradientDirection, Mask};
+pub use bitwise::{BitOperation, Bitwise, BitwiseOptions};
+pub use residual::{Residual, ResidualOperation, ResidualOptions};
+pub use databend::{AudioEffect, Databend, DatabendOptions, Length};
+pub use convert::{quantize, Convert, ConvertOptions};
+pub(crate) use convert::{to_rgb8, Color, Encoder};
+pub use geometry::{nearest_neighbor, Axis, Geometry, PadOptions, Rotation};
+pub use raw::{Raw, RawLayout};
+pub use diff::{byte_ranges, Diff, Difference, LineDifference};
+pub use channel_shift::{ChannelOffset, ChannelShift, ChannelShiftOptions};
+pub use sort::{sort_pixels, Sort, SortDirection, SortKey, SortOptions};
\ No newline at end of file
@@ -0,0 +1,129 @@
+use crate::operation::Domain;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+pub trait Bitwise {
+    fn bitwise(&mut self, options: &BitwiseOptions);
+}
+
+/// BitOperation specifies how each byte in scan lines is modified.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BitOperation {
+    /// Each bit is flipped with the given probability. NaN is treated as 0.
+    Flip { probability: f64, seed: u64 },
+    Xor(u8),
+    And(u8),
+    Or(u8),
+    RotateLeft(u32),
+    RotateRight(u32),
+    /// The bit planes specified by their positions are swapped. The position of the least significant bit is 0.
+    SwapPlanes(u32, u32),
+    /// Only the given number of the most significant bits are kept.
+    Crush(u32),
+}
+
+impl BitOperation {
+    pub(crate) fn apply(&self, bytes: &mut [u8]) {
+        match *self {
+            BitOperation::Flip { probability, seed } => {
+                let probability = if probability.is_nan() { 0.0 } else { probability.clamp(0.0, 1.0) };
+                let mut rng = StdRng::seed_from_u64(seed);
+                for byte in bytes.iter_mut() {
+                    for bit in 0..8 {
+                        if rng.gen_bool(probability) {
+                            *byte ^= 1 << bit;
+                        }
+                    }
+                }
+            }
+            operation => {
+                for byte in bytes.iter_mut() {
+                    *byte = operation.apply_to_byte(*byte);
+                }
+            }
+        }
+    }
+
+    fn apply_to_byte(&self, byte: u8) -> u8 {
+        match *self {
+            BitOperation::Flip { .. } => byte,
+            BitOperation::Xor(mask) => byte ^ mask,
+            BitOperation::And(mask) => byte & mask,
+            BitOperation::Or(mask) => byte | mask,
+            BitOperation::RotateLeft(bits) => byte.rotate_left(bits),
+            BitOperation::RotateRight(bits) => byte.rotate_right(bits),
+            BitOperation::SwapPlanes(a, b) => {
+                let (a, b) = (a % 8, b % 8);
+                let bit_a = (byte >> a) & 1;
+                let bit_b = (byte >> b) & 1;
+                if bit_a == bit_b {
+                    byte
+                } else {
+                    byte ^ (1 << a | 1 << b)
+                }
+            }
+            BitOperation::Crush(bits) => {
+                let bits = bits.min(8);
+                byte & !(u8::MAX.checked_shr(bits).unwrap_or(0))
+            }
+        }
+    }
+}
+
+/// BitwiseOptions describes which scan lines are modified by a bit operation.
+/// The filter type byte of each scan line is left untouched.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BitwiseOptions {
+    pub operation: BitOperation,
+    pub domain: Domain,
+    pub from: u32,
+    pub lines: u32,
+}
+
+impl BitwiseOptions {
+    pub fn new(operation: BitOperation) -> BitwiseOptions {
+        BitwiseOptions {
+            operation,
+            domain: Domain::default(),
+            from: 0,
+            lines: u32::MAX,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn apply(operation: BitOperation, byte: u8) -> u8 {
+        let mut bytes = [byte];
+        operation.apply(&mut bytes);
+        bytes[0]
+    }
+
+    #[test]
+    fn test_swap_planes() {
+        assert_eq!(0b1000_0000, apply(BitOperation::SwapPlanes(0, 7), 0b0000_0001));
+        assert_eq!(0b1000_0001, apply(BitOperation::SwapPlanes(0, 7), 0b1000_0001));
+    }
+
+    #[test]
+    fn test_crush() {
+        assert_eq!(0b1100_0000, apply(BitOperation::Crush(2), 0b1101_0110));
+        assert_eq!(0, apply(BitOperation::Crush(0), 0b1101_0110));
+        assert_eq!(0b1101_0110, apply(BitOperation::Crush(8), 0b1101_0110));
+    }
+
+    #[test]
+    fn test_flip() {
+        let mut bytes = [0b1010_1010; 16];
+        BitOperation::Flip { probability: 1.0, seed: 0 }.apply(&mut bytes);
+        assert!(bytes.iter().all(|byte| *byte == 0b0101_0101));
+
+        BitOperation::Flip { probability: 0.0, seed: 0 }.apply(&mut bytes);
+        assert!(bytes.iter().all(|byte| *byte == 0b0101_0101));
+
+        BitOperation::Flip { probability: f64::NAN, seed: 0 }.apply(&mut bytes);
+        assert!(bytes.iter().all(|byte| *byte == 0b0101_0101));
+    }
+}
@@ -0,0 +1,133 @@
+use crate::operation::{Domain, Region};
+
+pub trait MoveBlock {
+    fn move_block(&mut self, options: &BlockOptions);
+}
+
+/// BlockOperation specifies what is done with the source block.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum BlockOperation {
+    /// The source block is copied to the destination.
+    #[default]
+    Copy,
+    /// The source block and the block at the destination are exchanged.
+    Swap,
+    /// The source block is copied the given times, stepping by the distance from the source to the destination.
+    Repeat(u32),
+    /// The source block is dragged to the destination, leaving a copy on every pixel along the way.
+    Smear,
+}
+
+impl BlockOperation {
+    /// This method returns the positions where the source block is copied to.
+    /// Positions outside of the image of the given size are skipped without being enumerated,
+    /// so that the number of the positions is bounded by the size of the image.
+    pub(crate) fn destinations(&self, source: &Region, x: u32, y: u32, width: u32, height: u32) -> Vec<(u32, u32)> {
+        let dx = x as i64 - source.x as i64;
+        let dy = y as i64 - source.y as i64;
+        let (times, steps) = match self {
+            BlockOperation::Copy | BlockOperation::Swap => return vec![(x, y)],
+            BlockOperation::Repeat(_) if dx == 0 && dy == 0 => (1, 1),
+            BlockOperation::Repeat(times) => (*times as i64, 1),
+            BlockOperation::Smear => {
+                let steps = dx.abs().max(dy.abs()).max(1);
+                (steps, steps)
+            }
+        };
+        let at = |k: i64| {
+            let (k, steps) = (k as i128, steps as i128);
+            (source.x as i128 + dx as i128 * k / steps, source.y as i128 + dy as i128 * k / steps)
+        };
+        let (first, last) = inside(1, times + 1, |k| at(k).0, width as i128);
+        let (first, last) = inside(first, last, |k| at(k).1, height as i128);
+        (first..last).map(at).map(|(x, y)| (x as u32, y as u32)).collect()
+    }
+}
+
+/// Returns the range of `k` in `first..last` where `position(k)` is in `0..limit`.
+/// `position` must be monotonic, which makes the range contiguous and lets it be found by binary search.
+fn inside<F>(first: i64, last: i64, position: F, limit: i128) -> (i64, i64)
+where
+    F: Fn(i64) -> i128,
+{
+    if first >= last {
+        return (first, first);
+    }
+    let increasing = position(first) <= position(last - 1);
+    let start = partition_point(first, last, |k| if increasing { position(k) >= 0 } else { position(k) < limit });
+    let end = partition_point(start, last, |k| if increasing { position(k) >= limit } else { position(k) < 0 });
+    (start, end)
+}
+
+/// Returns the first `k` in `first..last` where the predicate holds, assuming it holds for all the following ones.
+fn partition_point(mut first: i64, mut last: i64, predicate: impl Fn(i64) -> bool) -> i64 {
+    while first < last {
+        let middle = first + (last - first) / 2;
+        if predicate(middle) {
+            last = middle;
+        } else {
+            first = middle + 1;
+        }
+    }
+    first
+}
+
+/// BlockOptions describes a block operation.
+///
+/// The source block is specified with `source`, and the destination is specified with `x` and `y` in pixels.
+/// Parts of blocks outside of the image are ignored.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BlockOptions {
+    pub operation: BlockOperation,
+    pub source: Region,
+    pub x: u32,
+    pub y: u32,
+    pub domain: Domain,
+}
+
+impl BlockOptions {
+    pub fn new(operation: BlockOperation, source: Region, x: u32, y: u32) -> BlockOptions {
+        BlockOptions {
+            operation,
+            source,
+            x,
+            y,
+            domain: Domain::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_repeat_destinations() {
+        let source = Region::new(10, 10, 4, 4);
+        let destinations = BlockOperation::Repeat(3).destinations(&source, 14, 12, 100, 100);
+        assert_eq!(vec![(14, 12), (18, 14), (22, 16)], destinations);
+    }
+
+    #[test]
+    fn test_smear_destinations() {
+        let source = Region::new(10, 10, 4, 4);
+        let destinations = BlockOperation::Smear.destinations(&source, 6, 12, 100, 100);
+        assert_eq!(vec![(9, 10), (8, 11), (7, 11), (6, 12)], destinations);
+    }
+
+    #[test]
+    fn test_destinations_are_clipped_to_image() {
+        let source = Region::new(10, 10, 4, 4);
+        let destinations = BlockOperation::Smear.destinations(&source, u32::MAX, 10, 20, 20);
+        assert_eq!((11..20).map(|x| (x, 10)).collect::<Vec<_>>(), destinations);
+
+        let destinations = BlockOperation::Repeat(u32::MAX).destinations(&source, 14, 12, 30, 30);
+        assert_eq!(vec![(14, 12), (18, 14), (22, 16), (26, 18)], destinations);
+        let destinations = BlockOperation::Repeat(u32::MAX).destinations(&source, 10, 10, 30, 30);
+        assert_eq!(vec![(10, 10)], destinations);
+
+        let source = Region::new(50, 5, 4, 4);
+        let destinations = BlockOperation::Repeat(u32::MAX).destinations(&source, 40, 5, 30, 30);
+        assert_eq!(vec![(20, 5), (10, 5), (0, 5)], destinations);
+    }
+}
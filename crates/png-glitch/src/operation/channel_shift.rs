@@ -0,0 +1,47 @@
+use crate::operation::EdgeMode;
+use crate::FilterType;
+
+pub trait ChannelShift {
+    fn shift_channels(&mut self, options: &ChannelShiftOptions);
+}
+
+/// ChannelOffset specifies how far the samples of a channel are moved.
+/// The channel is specified by its position in a pixel, e.g. 0 for red, 1 for green, 2 for blue, and 3 for alpha in true color with alpha images.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ChannelOffset {
+    pub channel: usize,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl ChannelOffset {
+    pub fn new(channel: usize, x: i32, y: i32) -> ChannelOffset {
+        ChannelOffset { channel, x, y }
+    }
+}
+
+/// ChannelShiftOptions describes how the channels are shifted.
+///
+/// Only the scan lines from `from` to `from + lines - 1` are updated, while samples can be taken from any scan line in the image.
+/// Samples are shifted without filters, and then the updated scan lines are filtered with `filter_type` if it is specified,
+/// or with their own filter types otherwise. The other scan lines, including their filter types, are left as they were.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelShiftOptions {
+    pub offsets: Vec<ChannelOffset>,
+    pub edge_mode: EdgeMode,
+    pub from: u32,
+    pub lines: u32,
+    pub filter_type: Option<FilterType>,
+}
+
+impl ChannelShiftOptions {
+    pub fn new(offsets: Vec<ChannelOffset>) -> ChannelShiftOptions {
+        ChannelShiftOptions {
+            offsets,
+            edge_mode: EdgeMode::default(),
+            from: 0,
+            lines: u32::MAX,
+            filter_type: None,
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use crate::operation::SortKey;
+use crate::png::max_sample;
+use crate::{ColorType, Error, Pixel};
+use std::collections::HashMap;
+
+pub trait Convert {
+    fn convert(&mut self, options: &ConvertOptions) -> Result<(), Error>;
+}
+
+/// ConvertOptions specifies the color type and the bit depth which the image is converted into.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ConvertOptions {
+    pub color_type: ColorType,
+    pub bit_depth: u8,
+    /// The number of colors in the palette on converting into index color.
+    /// It is limited to the number of colors the bit depth can express.
+    pub colors: usize,
+}
+
+impl ConvertOptions {
+    pub fn new(color_type: ColorType, bit_depth: u8) -> ConvertOptions {
+        ConvertOptions {
+            color_type,
+            bit_depth,
+            colors: usize::MAX,
+        }
+    }
+
+    /// This method returns the number of colors in the palette, which is at least 1.
+    pub fn palette_size(&self) -> usize {
+        self.colors.clamp(1, 1 << self.bit_depth.min(8))
+    }
+}
+
+/// Color is the color of a pixel independent from the color type and the bit depth.
+/// Each channel is in the range of `0.0..=1.0`.
+pub(crate) type Color = [f32; 4];
+
+/// Encoder converts colors into the pixels in the color type and the bit depth.
+pub(crate) struct Encoder<'a> {
+    color_type: ColorType,
+    max: f32,
+    palette: &'a [[u8; 3]],
+    indices: HashMap<[u8; 3], u16>,
+}
+
+impl<'a> Encoder<'a> {
+    pub fn new(color_type: ColorType, bit_depth: u8, palette: &'a [[u8; 3]]) -> Encoder<'a> {
+        Encoder {
+            color_type,
+            max: max_sample(bit_depth) as f32,
+            palette,
+            indices: HashMap::new(),
+        }
+    }
+
+    pub fn encode(&mut self, [r, g, b, a]: Color) -> Pixel {
+        let sample = |value: f32| (value * self.max).round() as u16;
+        match self.color_type {
+            ColorType::GrayScale => Pixel::new(&[sample(SortKey::Brightness.evaluate([r, g, b]))]),
+            ColorType::GrayScaleAlpha => Pixel::new(&[sample(SortKey::Brightness.evaluate([r, g, b])), sample(a)]),
+            ColorType::TrueColor => Pixel::new(&[sample(r), sample(g), sample(b)]),
+            ColorType::TrueColorAlpha => Pixel::new(&[sample(r), sample(g), sample(b), sample(a)]),
+            ColorType::IndexColor => {
+                let rgb = to_rgb8([r, g, b, a]);
+                let palette = self.palette;
+                let index = *self.indices.entry(rgb).or_insert_with(|| nearest(palette, rgb));
+                Pixel::new(&[index])
+            }
+        }
+    }
+}
+
+pub(crate) fn to_rgb8([r, g, b, _]: Color) -> [u8; 3] {
+    [r, g, b].map(|value| (value * 255.0).round() as u8)
+}
+
+fn nearest(palette: &[[u8; 3]], rgb: [u8; 3]) -> u16 {
+    let distance = |color: &[u8; 3]| -> u32 {
+        color.iter().zip(rgb.iter()).map(|(a, b)| (*a as i32 - *b as i32).pow(2) as u32).sum()
+    };
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, color)| distance(color))
+        .map(|(index, _)| index as u16)
+        .unwrap_or_default()
+}
+
+/// This function reduces the colors into a palette of `size` colors at most with the median cut algorithm.
+/// The colors are put into a box, and the box with the widest range of a channel is split at its median,
+/// until there are as many boxes as the size. Each color in the palette is the average of the colors in a box.
+/// The colors are kept as they are if there are fewer distinct colors than the size.
+pub fn quantize(colors: &[[u8; 3]], size: usize) -> Vec<[u8; 3]> {
+    let mut histogram: HashMap<[u8; 3], usize> = HashMap::new();
+    for color in colors {
+        *histogram.entry(*color).or_default() += 1;
+    }
+    let mut boxes: Vec<Vec<([u8; 3], usize)>> = vec![histogram.into_iter().collect()];
+    while boxes.len() < size.max(1) {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .flat_map(|(index, colors)| (0..3).map(move |channel| (index, channel, range(colors, channel))))
+            .max_by_key(|(_, _, range)| *range);
+        let Some((index, channel, _)) = widest else {
+            break;
+        };
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_by_key(|(color, _)| (color[channel], *color));
+        let upper = colors.split_off(colors.len() / 2);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+    let mut palette: Vec<[u8; 3]> = boxes.iter().filter(|colors| !colors.is_empty()).map(|colors| average(colors)).collect();
+    palette.sort();
+    palette
+}
+
+fn range(colors: &[([u8; 3], usize)], channel: usize) -> u8 {
+    let values = colors.iter().map(|(color, _)| color[channel]);
+    values.clone().max().unwrap_or_default() - values.min().unwrap_or_default()
+}
+
+fn average(colors: &[([u8; 3], usize)]) -> [u8; 3] {
+    let total: usize = colors.iter().map(|(_, count)| count).sum();
+    let mut average = [0; 3];
+    for (channel, value) in average.iter_mut().enumerate() {
+        let sum: usize = colors.iter().map(|(color, count)| color[channel] as usize * count).sum();
+        *value = ((sum + total / 2) / total.max(1)) as u8;
+    }
+    average
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_palette_size() {
+        assert_eq!(4, ConvertOptions::new(ColorType::IndexColor, 2).palette_size());
+        assert_eq!(256, ConvertOptions::new(ColorType::IndexColor, 8).palette_size());
+        let options = ConvertOptions {
+            colors: 0,
+            ..ConvertOptions::new(ColorType::IndexColor, 8)
+        };
+        assert_eq!(1, options.palette_size());
+    }
+
+    #[test]
+    fn test_quantize_keeps_few_colors() {
+        let colors = [[255, 0, 0], [0, 0, 255], [255, 0, 0]];
+        assert_eq!(vec![[0, 0, 255], [255, 0, 0]], quantize(&colors, 16));
+    }
+
+    #[test]
+    fn test_quantize_merges_close_colors() {
+        let colors = [[0, 0, 0], [10, 0, 0], [200, 200, 200], [210, 200, 200]];
+        assert_eq!(vec![[5, 0, 0], [205, 200, 200]], quantize(&colors, 2));
+        assert_eq!(vec![[105, 100, 100]], quantize(&colors, 1));
+    }
+
+    #[test]
+    fn test_encode() {
+        let white = [1.0, 1.0, 1.0, 0.5];
+        assert_eq!(Pixel::new(&[255]), Encoder::new(ColorType::GrayScale, 8, &[]).encode(white));
+        assert_eq!(Pixel::new(&[65535, 32768]), Encoder::new(ColorType::GrayScaleAlpha, 16, &[]).encode(white));
+        assert_eq!(Pixel::new(&[255, 255, 255]), Encoder::new(ColorType::TrueColor, 8, &[]).encode(white));
+
+        let palette = [[0, 0, 0], [250, 250, 250]];
+        assert_eq!(Pixel::new(&[1]), Encoder::new(ColorType::IndexColor, 1, &palette).encode(white));
+    }
+}
@@ -0,0 +1,207 @@
+use crate::operation::Domain;
+
+pub trait Databend {
+    fn databend(&mut self, options: &DatabendOptions);
+}
+
+/// Length specifies a duration of the signal.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Length {
+    /// The length in samples. Each byte of the scan lines is a sample.
+    Samples(usize),
+    /// The length in pixels. A pixel is as long as its bytes, or a byte in the images with less than 8 bits per pixel.
+    Pixels(usize),
+    /// The length in scan lines. Delays of whole scan lines make the effect appear vertically.
+    Lines(usize),
+}
+
+impl Length {
+    /// This method returns the length in samples.
+    pub fn samples(&self, bytes_per_pixel: usize, bytes_per_line: usize) -> usize {
+        match *self {
+            Length::Samples(samples) => samples,
+            Length::Pixels(pixels) => pixels.saturating_mul(bytes_per_pixel),
+            Length::Lines(lines) => lines.saturating_mul(bytes_per_line),
+        }
+    }
+}
+
+/// AudioEffect specifies how the bytes of the scan lines are processed as an audio signal,
+/// as if they were imported into an audio editor as unsigned 8-bit samples.
+/// `mix` is the ratio of the processed signal mixed into the output, from 0.0 to 1.0.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AudioEffect {
+    /// The signal is repeated after the delay, and each repetition is multiplied by the feedback.
+    Echo { delay: Length, feedback: f32, mix: f32 },
+    /// The signal is reverberated by four comb filters and two all-pass filters, known as Schroeder reverberator.
+    /// The room size is the delay of the shortest comb filter, and the decay is the feedback of the comb filters.
+    Reverb { room_size: Length, decay: f32, mix: f32 },
+    /// Each sample is quantized to the given number of bits, and the first sample in each `hold` is repeated.
+    Bitcrush { bits: u32, hold: Length },
+    /// The signal is smoothed by a one-pole low-pass filter, whose time constant is the given length.
+    LowPass(Length),
+    /// The signal smoothed by a one-pole low-pass filter is subtracted, so that only the edges are left.
+    HighPass(Length),
+    /// The signal is amplified by the gain and clipped softly.
+    Distortion { gain: f32 },
+}
+
+impl AudioEffect {
+    pub(crate) fn apply(&self, bytes: &mut [u8], bytes_per_pixel: usize, bytes_per_line: usize) {
+        let samples = |length: Length| length.samples(bytes_per_pixel, bytes_per_line);
+        let signal: Vec<f32> = bytes.iter().map(|byte| (*byte as f32 - 128.0) / 128.0).collect();
+        let output = match *self {
+            AudioEffect::Echo { delay, feedback, mix } => {
+                let echo = comb(&signal, samples(delay), feedback);
+                blend(&signal, &echo, mix)
+            }
+            AudioEffect::Reverb { room_size, decay, mix } => {
+                let room_size = samples(room_size).max(1) as f32;
+                let combs: Vec<Vec<f32>> = [1.0, 1.13, 1.27, 1.41]
+                    .iter()
+                    .map(|ratio| comb(&signal, (room_size * ratio) as usize, decay))
+                    .collect();
+                let wet: Vec<f32> = (0..signal.len())
+                    .map(|index| combs.iter().map(|comb| comb[index]).sum::<f32>() / combs.len() as f32)
+                    .collect();
+                let wet = all_pass(&wet, (room_size / 3.0) as usize, 0.5);
+                let wet = all_pass(&wet, (room_size / 9.0) as usize, 0.5);
+                blend(&signal, &wet, mix)
+            }
+            AudioEffect::Bitcrush { bits, hold } => {
+                let step = 2.0 / 2f32.powi(bits.clamp(1, 8) as i32);
+                let hold = samples(hold).max(1);
+                (0..signal.len())
+                    .map(|index| signal[index - index % hold])
+                    .map(|sample| (sample / step).floor() * step)
+                    .collect()
+            }
+            AudioEffect::LowPass(length) => low_pass(&signal, samples(length)),
+            AudioEffect::HighPass(length) => {
+                let low = low_pass(&signal, samples(length));
+                signal.iter().zip(low.iter()).map(|(sample, low)| sample - low).collect()
+            }
+            AudioEffect::Distortion { gain } => signal.iter().map(|sample| (sample * gain).tanh()).collect(),
+        };
+        for (byte, sample) in bytes.iter_mut().zip(output) {
+            *byte = (sample * 128.0 + 128.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+/// Feedback comb filter: `y[n] = x[n] + feedback * y[n - delay]`.
+fn comb(signal: &[f32], delay: usize, feedback: f32) -> Vec<f32> {
+    let mut output = signal.to_vec();
+    if delay == 0 {
+        return output;
+    }
+    for index in delay..output.len() {
+        output[index] += feedback * output[index - delay];
+    }
+    output
+}
+
+/// Schroeder all-pass filter: `y[n] = -gain * x[n] + x[n - delay] + gain * y[n - delay]`.
+fn all_pass(signal: &[f32], delay: usize, gain: f32) -> Vec<f32> {
+    let delay = delay.max(1);
+    let mut output = vec![0.0; signal.len()];
+    for index in 0..signal.len() {
+        let delayed = if index >= delay {
+            signal[index - delay] + gain * output[index - delay]
+        } else {
+            0.0
+        };
+        output[index] = delayed - gain * signal[index];
+    }
+    output
+}
+
+fn low_pass(signal: &[f32], length: usize) -> Vec<f32> {
+    let alpha = 1.0 / (length as f32 + 1.0);
+    let mut state = signal.first().copied().unwrap_or_default();
+    signal
+        .iter()
+        .map(|sample| {
+            state += alpha * (sample - state);
+            state
+        })
+        .collect()
+}
+
+fn blend(dry: &[f32], wet: &[f32], mix: f32) -> Vec<f32> {
+    let mix = mix.clamp(0.0, 1.0);
+    dry.iter().zip(wet.iter()).map(|(dry, wet)| dry * (1.0 - mix) + wet * mix).collect()
+}
+
+/// DatabendOptions describes which scan lines are processed by an audio effect.
+/// The bytes of the scan lines are concatenated into a signal, and the filter type byte of each scan line is left untouched.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DatabendOptions {
+    pub effect: AudioEffect,
+    pub domain: Domain,
+    pub from: u32,
+    pub lines: u32,
+}
+
+impl DatabendOptions {
+    pub fn new(effect: AudioEffect) -> DatabendOptions {
+        DatabendOptions {
+            effect,
+            domain: Domain::default(),
+            from: 0,
+            lines: u32::MAX,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn apply(effect: AudioEffect, bytes: &[u8]) -> Vec<u8> {
+        let mut bytes = bytes.to_vec();
+        effect.apply(&mut bytes, 3, 6);
+        bytes
+    }
+
+    #[test]
+    fn test_length() {
+        assert_eq!(2, Length::Samples(2).samples(3, 6));
+        assert_eq!(6, Length::Pixels(2).samples(3, 6));
+        assert_eq!(12, Length::Lines(2).samples(3, 6));
+    }
+
+    #[test]
+    fn test_echo() {
+        let echo = AudioEffect::Echo { delay: Length::Pixels(1), feedback: 0.5, mix: 1.0 };
+        assert_eq!(vec![192, 128, 128, 160, 128, 128, 144], apply(echo, &[192, 128, 128, 128, 128, 128, 128]));
+    }
+
+    #[test]
+    fn test_bitcrush() {
+        let bitcrush = AudioEffect::Bitcrush { bits: 1, hold: Length::Samples(2) };
+        assert_eq!(vec![128, 128, 0, 0], apply(bitcrush, &[200, 10, 20, 250]));
+    }
+
+    #[test]
+    fn test_filters() {
+        let constant = [100; 8];
+        assert_eq!(constant.to_vec(), apply(AudioEffect::LowPass(Length::Samples(4)), &constant));
+        assert_eq!(vec![128; 8], apply(AudioEffect::HighPass(Length::Samples(4)), &constant));
+
+        let smoothed = apply(AudioEffect::LowPass(Length::Samples(1)), &[0, 255, 0, 255]);
+        assert_eq!(vec![0, 128, 64, 159], smoothed);
+    }
+
+    #[test]
+    fn test_distortion() {
+        let distorted = apply(AudioEffect::Distortion { gain: 10.0 }, &[0, 120, 128, 136, 255]);
+        assert_eq!(vec![0, 57, 128, 199, 255], distorted);
+    }
+
+    #[test]
+    fn test_reverb_keeps_silence() {
+        let reverb = AudioEffect::Reverb { room_size: Length::Samples(10), decay: 0.8, mix: 0.5 };
+        assert_eq!(vec![128; 64], apply(reverb, &[128; 64]));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::png::Png;
+use crate::{ColorType, Error, FilterType, ParseOptions, PngGlitch};
+use std::ops::Range;
+
+pub trait Diff {
+    fn diff(&self, other: &Self) -> Option<Difference>;
+}
+
+/// LineDifference describes how a scan line differs between two images.
+/// Byte ranges are counted from the first byte after the filter type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineDifference {
+    /// The index of the scan line.
+    pub index: u32,
+    /// The filter types of the scan line in the original image and the other one.
+    pub filter_types: (FilterType, FilterType),
+    /// The byte ranges which differ in the filtered scan lines, as they are stored in the images.
+    pub filtered: Vec<Range<usize>>,
+    /// The byte ranges which differ after removing filters from the scan lines.
+    pub unfiltered: Vec<Range<usize>>,
+}
+
+/// Difference is the result of comparing two images of the same size, color type, and bit depth.
+/// It holds the scan lines which differ, and how much each pixel differs after removing filters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difference {
+    width: u32,
+    height: u32,
+    lines: Vec<LineDifference>,
+    magnitudes: Vec<u8>,
+    /// The limit of the decoded size of the compared image, which the heatmap is created with.
+    max_decoded_size: usize,
+}
+
+impl Difference {
+    pub(crate) fn new(width: u32, height: u32, lines: Vec<LineDifference>, magnitudes: Vec<u8>, max_decoded_size: usize) -> Difference {
+        Difference {
+            width,
+            height,
+            lines,
+            magnitudes,
+            max_decoded_size,
+        }
+    }
+
+    /// This method returns the scan lines which differ in their filter types, filtered bytes, or unfiltered bytes.
+    pub fn lines(&self) -> &[LineDifference] {
+        &self.lines
+    }
+
+    /// This method returns true if the images are identical.
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// This method returns the number of bytes which differ in the filtered scan lines.
+    pub fn filtered_bytes(&self) -> usize {
+        self.lines.iter().flat_map(|line| line.filtered.iter()).map(|range| range.len()).sum()
+    }
+
+    /// This method returns the number of bytes which differ in the unfiltered scan lines.
+    pub fn unfiltered_bytes(&self) -> usize {
+        self.lines.iter().flat_map(|line| line.unfiltered.iter()).map(|range| range.len()).sum()
+    }
+
+    /// This method returns how much the pixel at (x, y) differs, from 0 to 255.
+    /// The value is the largest difference of the samples in the pixel, scaled to 8 bits.
+    pub fn magnitude(&self, x: u32, y: u32) -> Option<u8> {
+        if x < self.width && y < self.height {
+            self.magnitudes.get(y as usize * self.width as usize + x as usize).copied()
+        } else {
+            None
+        }
+    }
+
+    /// This method creates an 8-bit RGB image showing the magnitude of the difference of each pixel.
+    /// Identical pixels are black, and the color goes through red and yellow to white as the difference grows.
+    ///
+    /// [Error::Operation] with [crate::OperationError::TooLargeImage] is returned when the heatmap is too large to create,
+    /// which can happen even if the compared images are not, since they may have fewer bits per pixel.
+    /// The size is limited with `ParseOptions::max_decoded_size` of the image [PngGlitch::diff] is called on.
+    pub fn heatmap(&self) -> Result<PngGlitch, Error> {
+        let size = Png::created_size(self.width, self.height, ColorType::TrueColor, 8, self.max_decoded_size)?;
+        let mut data = Vec::with_capacity(size);
+        for line in self.magnitudes.chunks(self.width.max(1) as usize) {
+            data.push(FilterType::None.into());
+            data.extend(line.iter().flat_map(|magnitude| heat(*magnitude)));
+        }
+        let options = ParseOptions { max_decoded_size: self.max_decoded_size, ..ParseOptions::default() };
+        let png = Png::create(self.width, self.height, ColorType::TrueColor, 8, vec![], data, &options)?;
+        Ok(PngGlitch { png })
+    }
+}
+
+fn heat(magnitude: u8) -> [u8; 3] {
+    let value = magnitude as u32 * 3;
+    let channel = |offset: u32| value.saturating_sub(offset).min(u8::MAX as u32) as u8;
+    [channel(0), channel(255), channel(510)]
+}
+
+/// This function returns the ranges of the bytes which differ between the slices.
+pub fn byte_ranges(a: &[u8], b: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = vec![];
+    for (index, _) in a.iter().zip(b.iter()).enumerate().filter(|(_, (a, b))| a != b) {
+        match ranges.last_mut() {
+            Some(range) if range.end == index => range.end = index + 1,
+            _ => ranges.push(index..index + 1),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::OperationError;
+
+    #[test]
+    fn test_byte_ranges() {
+        assert_eq!(Vec::<Range<usize>>::new(), byte_ranges(&[1, 2, 3], &[1, 2, 3]));
+        assert_eq!(vec![0..2, 3..4], byte_ranges(&[1, 2, 3, 4], &[0, 0, 3, 0]));
+    }
+
+    #[test]
+    fn test_heat() {
+        assert_eq!([0, 0, 0], heat(0));
+        assert_eq!([255, 0, 0], heat(85));
+        assert_eq!([255, 255, 0], heat(170));
+        assert_eq!([255, 255, 255], heat(255));
+    }
+
+    #[test]
+    fn test_too_large_heatmap() {
+        let difference = Difference::new(20000, 20000, vec![], vec![], ParseOptions::default().max_decoded_size);
+        match difference.heatmap() {
+            Err(Error::Operation(error)) => assert_eq!(OperationError::TooLargeImage, error),
+            other => panic!("The heatmap should be rejected without a parse error: {:?}", other.err()),
+        }
+    }
+}
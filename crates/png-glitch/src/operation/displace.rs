@@ -0,0 +1,127 @@
+use crate::operation::{Domain, EdgeMode};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f32::consts::PI;
+
+pub trait Displace {
+    fn displace(&mut self, options: &DisplaceOptions);
+}
+
+/// Displacement specifies how many pixels each scan line is shifted to the right.
+/// Negative amounts shift scan lines to the left.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Displacement {
+    /// All scan lines are shifted by the same amount.
+    Constant(i32),
+    /// Each scan line is shifted by a random amount in the range of `-max..=max`.
+    Random { max: u32, seed: u64 },
+    /// The amount follows a sine wave whose period is specified in scan lines.
+    Sine { amplitude: f32, period: f32, phase: f32 },
+    /// The amount follows a smooth noise whose features are `scale` scan lines apart.
+    Noise { amplitude: f32, scale: f32, seed: u64 },
+}
+
+impl Displacement {
+    /// This method returns the amounts for the scan lines from `from` to `from + lines - 1`.
+    pub fn amounts(&self, from: usize, lines: usize) -> Vec<i64> {
+        let range = from..from + lines;
+        match *self {
+            Displacement::Constant(amount) => vec![amount as i64; lines],
+            Displacement::Random { max, seed } => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let max = max as i64;
+                range.map(|_| rng.gen_range(-max..=max)).collect()
+            }
+            Displacement::Sine { amplitude, period, phase } => {
+                let period = if period == 0.0 { 1.0 } else { period };
+                range
+                    .map(|line| amplitude * (2.0 * PI * line as f32 / period + phase).sin())
+                    .map(|amount| amount.round() as i64)
+                    .collect()
+            }
+            Displacement::Noise { amplitude, scale, seed } => {
+                let scale = scale.max(1.0);
+                // The lattice covers only the displaced scan lines. Each point is derived from its index,
+                // so that a scan line is displaced by the same amount regardless of `from`.
+                let first = (from as f32 / scale).floor() as usize;
+                let last = ((from + lines) as f32 / scale).ceil() as usize + 1;
+                let lattice: Vec<f32> = (first..=last).map(|index| lattice_point(seed, index)).collect();
+                range
+                    .map(|line| {
+                        let position = line as f32 / scale;
+                        let index = (position.floor() as usize).saturating_sub(first).min(lattice.len() - 2);
+                        let t = position.fract();
+                        let t = t * t * (3.0 - 2.0 * t);
+                        let value = lattice[index] * (1.0 - t) + lattice[index + 1] * t;
+                        (amplitude * value).round() as i64
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Returns the value of the noise lattice at the given index, in the range of `-1.0..=1.0`.
+fn lattice_point(seed: u64, index: usize) -> f32 {
+    let mut rng = StdRng::seed_from_u64(seed.wrapping_add((index as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)));
+    rng.gen_range(-1.0..=1.0)
+}
+
+/// DisplaceOptions describes how scan lines are displaced.
+///
+/// The scan lines from `from` to `from + lines - 1` are shifted horizontally in whole pixels.
+/// Pixels shifted in from outside of the scan line are chosen according to `edge_mode`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DisplaceOptions {
+    pub displacement: Displacement,
+    pub edge_mode: EdgeMode,
+    pub domain: Domain,
+    pub from: u32,
+    pub lines: u32,
+}
+
+impl DisplaceOptions {
+    pub fn new(displacement: Displacement) -> DisplaceOptions {
+        DisplaceOptions {
+            displacement,
+            edge_mode: EdgeMode::default(),
+            domain: Domain::default(),
+            from: 0,
+            lines: u32::MAX,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sine() {
+        let amounts = Displacement::Sine { amplitude: 10.0, period: 4.0, phase: 0.0 }.amounts(0, 4);
+        assert_eq!(vec![0, 10, 0, -10], amounts);
+    }
+
+    #[test]
+    fn test_random_is_reproducible() {
+        let displacement = Displacement::Random { max: 5, seed: 42 };
+        let amounts = displacement.amounts(0, 100);
+        assert_eq!(amounts, displacement.amounts(0, 100));
+        assert!(amounts.iter().all(|amount| (-5..=5).contains(amount)));
+    }
+
+    #[test]
+    fn test_noise_is_bounded() {
+        let amounts = Displacement::Noise { amplitude: 8.0, scale: 16.0, seed: 1 }.amounts(10, 200);
+        assert_eq!(200, amounts.len());
+        assert!(amounts.iter().all(|amount| (-8..=8).contains(amount)));
+    }
+
+    #[test]
+    fn test_noise_depends_only_on_line() {
+        let displacement = Displacement::Noise { amplitude: 8.0, scale: 16.0, seed: 1 };
+        assert_eq!(displacement.amounts(0, 200)[50..], displacement.amounts(50, 150));
+        let far = u32::MAX as usize - 10;
+        assert_eq!(10, displacement.amounts(far, 10).len());
+    }
+}
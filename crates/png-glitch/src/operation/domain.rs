@@ -0,0 +1,10 @@
+/// Domain specifies which representation of the scan lines an operation edits.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Domain {
+    /// The bytes are edited as they are. Edits on filtered bytes propagate through the filters.
+    #[default]
+    Filtered,
+    /// The bytes are edited without filters, and then the edited scan lines are filtered again with their own filter types.
+    /// The other scan lines, including their filter types, are left as they were.
+    Unfiltered,
+}
@@ -0,0 +1,43 @@
+/// EdgeMode specifies how positions outside of the image are handled when pixels are moved.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum EdgeMode {
+    /// Positions wrap around to the opposite edge.
+    #[default]
+    Wrap,
+    /// Positions are clamped to the nearest edge.
+    Clamp,
+    /// Samples outside of the image are taken as zero.
+    Zero,
+}
+
+impl EdgeMode {
+    /// This method maps the position into the range of `0..length`.
+    /// `None` is returned when the sample at the position should be taken as zero.
+    pub fn resolve(&self, position: i64, length: usize) -> Option<usize> {
+        if length == 0 {
+            return None;
+        }
+        let length = length as i64;
+        match self {
+            EdgeMode::Wrap => Some(position.rem_euclid(length) as usize),
+            EdgeMode::Clamp => Some(position.clamp(0, length - 1) as usize),
+            EdgeMode::Zero if (0..length).contains(&position) => Some(position as usize),
+            EdgeMode::Zero => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(Some(8), EdgeMode::Wrap.resolve(-2, 10));
+        assert_eq!(Some(1), EdgeMode::Wrap.resolve(11, 10));
+        assert_eq!(Some(0), EdgeMode::Clamp.resolve(-2, 10));
+        assert_eq!(Some(9), EdgeMode::Clamp.resolve(11, 10));
+        assert_eq!(None, EdgeMode::Zero.resolve(-2, 10));
+        assert_eq!(Some(5), EdgeMode::Zero.resolve(5, 10));
+    }
+}
@@ -1,5 +1,5 @@
 use std::io::Write;
 
 pub trait Encode {
-    fn encode(&self, buffer: impl Write) -> anyhow::Result<()>;
+    fn encode(&self, buffer: impl Write) -> std::io::Result<()>;
 }
\ No newline at end of file
@@ -0,0 +1,12 @@
+use crate::operation::FilterStrategy;
+use crate::FilterType;
+
+pub trait Filter {
+    fn remove_filter_from(&mut self, from: usize, lines: usize);
+
+    fn apply_filter_from(&mut self, filter_type: FilterType, from: usize, lines: usize);
+
+    fn apply_filter_plan(&mut self, filter_types: &[FilterType], from: usize);
+
+    fn apply_filter_strategy(&mut self, strategy: &FilterStrategy, from: usize, lines: usize) -> Vec<FilterType>;
+}
@@ -0,0 +1,71 @@
+use crate::FilterType;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// FilterStrategy specifies how the filter type of each scan line is chosen.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FilterStrategy {
+    /// All scan lines are filtered with the same filter type.
+    Fixed(FilterType),
+    /// Filter types are taken from the pattern in turn, starting from the first scan line of the image.
+    Pattern(Vec<FilterType>),
+    /// Filter types are chosen randomly according to their weights.
+    Random { weights: Vec<(FilterType, u32)>, seed: u64 },
+    /// The filter type minimizing the sum of absolute differences is chosen for each scan line, as libpng does.
+    /// This heuristic usually gives the best compression.
+    MinimumSumOfAbsoluteDifferences,
+}
+
+impl FilterStrategy {
+    /// This method returns the filter types for the scan lines from `from` to `from + lines - 1`.
+    /// `None` is returned when the filter types depend on the pixels.
+    pub(crate) fn plan(&self, from: usize, lines: usize) -> Option<Vec<FilterType>> {
+        match self {
+            FilterStrategy::Fixed(filter_type) => Some(vec![*filter_type; lines]),
+            FilterStrategy::Pattern(pattern) if pattern.is_empty() => Some(vec![FilterType::None; lines]),
+            FilterStrategy::Pattern(pattern) => {
+                Some((from..from + lines).map(|index| pattern[index % pattern.len()]).collect())
+            }
+            FilterStrategy::Random { weights, seed } => {
+                let mut rng = StdRng::seed_from_u64(*seed);
+                let plan = match WeightedIndex::new(weights.iter().map(|(_, weight)| *weight)) {
+                    Ok(distribution) => (0..lines).map(|_| weights[distribution.sample(&mut rng)].0).collect(),
+                    Err(_) => vec![FilterType::None; lines],
+                };
+                Some(plan)
+            }
+            FilterStrategy::MinimumSumOfAbsoluteDifferences => None,
+        }
+    }
+}
+
+impl From<FilterType> for FilterStrategy {
+    fn from(value: FilterType) -> Self {
+        FilterStrategy::Fixed(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pattern() {
+        let strategy = FilterStrategy::Pattern(vec![FilterType::Sub, FilterType::Up, FilterType::Paeth]);
+        let expected = vec![FilterType::Paeth, FilterType::Sub, FilterType::Up, FilterType::Paeth];
+        assert_eq!(Some(expected), strategy.plan(2, 4));
+    }
+
+    #[test]
+    fn test_random() {
+        let strategy = FilterStrategy::Random {
+            weights: vec![(FilterType::Sub, 1), (FilterType::Up, 0), (FilterType::Average, 3)],
+            seed: 7,
+        };
+        let plan = strategy.plan(0, 100).unwrap_or_default();
+        assert_eq!(100, plan.len());
+        assert!(plan.iter().all(|filter_type| *filter_type == FilterType::Sub || *filter_type == FilterType::Average));
+        assert_eq!(strategy.plan(0, 100), Some(plan));
+    }
+}
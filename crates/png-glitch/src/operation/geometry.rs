@@ -0,0 +1,103 @@
+use crate::operation::Region;
+use crate::{Error, Pixel};
+
+pub trait Geometry {
+    fn crop(&mut self, region: &Region) -> Result<(), Error>;
+    fn pad(&mut self, options: &PadOptions) -> Result<(), Error>;
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), Error>;
+    fn rotate(&mut self, rotation: Rotation) -> Result<(), Error>;
+    fn flip(&mut self, axis: Axis) -> Result<(), Error>;
+}
+
+/// Rotation specifies the angle to rotate an image by.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    /// 90 degrees clockwise.
+    Clockwise,
+    /// 180 degrees.
+    HalfTurn,
+    /// 90 degrees counterclockwise.
+    CounterClockwise,
+}
+
+impl Rotation {
+    /// This method returns the size of the image with the given size after the rotation.
+    pub fn size(&self, width: u32, height: u32) -> (u32, u32) {
+        match self {
+            Rotation::HalfTurn => (width, height),
+            Rotation::Clockwise | Rotation::CounterClockwise => (height, width),
+        }
+    }
+
+    /// This method returns the position in the original image of the pixel at (x, y) in the rotated one.
+    pub fn source(&self, width: u32, height: u32, x: u32, y: u32) -> (u32, u32) {
+        match self {
+            Rotation::Clockwise => (y, height - 1 - x),
+            Rotation::HalfTurn => (width - 1 - x, height - 1 - y),
+            Rotation::CounterClockwise => (width - 1 - y, x),
+        }
+    }
+}
+
+/// Axis specifies the direction to flip an image.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Axis {
+    /// The image is mirrored left to right.
+    Horizontal,
+    /// The image is turned upside down.
+    Vertical,
+}
+
+/// PadOptions describes the margins added around an image, and the pixel filling them.
+/// The pixel holds the raw samples in the color type and the bit depth of the image.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PadOptions {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+    pub pixel: Pixel,
+}
+
+impl PadOptions {
+    /// This method creates options to add the margin of the same size on every side.
+    pub fn new(margin: u32) -> PadOptions {
+        PadOptions {
+            top: margin,
+            right: margin,
+            bottom: margin,
+            left: margin,
+            pixel: Pixel::default(),
+        }
+    }
+}
+
+/// This function maps a position in the resized image to the nearest pixel in the original one.
+pub fn nearest_neighbor(position: u32, resized: u32, original: u32) -> u32 {
+    let source = (position as u64 * 2 + 1) * original as u64 / (resized as u64 * 2);
+    source.min(original.saturating_sub(1) as u64) as u32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rotation() {
+        assert_eq!((2, 3), Rotation::Clockwise.size(3, 2));
+        assert_eq!((3, 2), Rotation::HalfTurn.size(3, 2));
+        // The top-left pixel of the rotated image comes from the bottom-left of the original one, and so on.
+        assert_eq!((0, 1), Rotation::Clockwise.source(3, 2, 0, 0));
+        assert_eq!((2, 1), Rotation::HalfTurn.source(3, 2, 0, 0));
+        assert_eq!((2, 0), Rotation::CounterClockwise.source(3, 2, 0, 0));
+        assert_eq!((2, 0), Rotation::Clockwise.source(3, 2, 1, 2));
+    }
+
+    #[test]
+    fn test_nearest_neighbor() {
+        let scaled: Vec<u32> = (0..6).map(|x| nearest_neighbor(x, 6, 3)).collect();
+        assert_eq!(vec![0, 0, 1, 1, 2, 2], scaled);
+        let reduced: Vec<u32> = (0..2).map(|x| nearest_neighbor(x, 2, 6)).collect();
+        assert_eq!(vec![1, 4], reduced);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::operation::Region;
+use crate::{Error, PngGlitch};
+use std::path::Path;
+
+const BAYER_MATRIX: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// GradientDirection specifies how the weight of a gradient mask changes.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum GradientDirection {
+    /// The weight increases from the left edge to the right edge.
+    #[default]
+    Horizontal,
+    /// The weight increases from the top edge to the bottom edge.
+    Vertical,
+    /// The weight decreases from the center to the corners.
+    Radial,
+}
+
+/// Mask specifies which pixels an operation is allowed to change.
+///
+/// Each pixel in a mask has a weight from 0 to 255. Pixels with weight 255 are always changed,
+/// and the ones with weight 0 are never changed. Pixels with intermediate weights are chosen with ordered dithering,
+/// so that the ratio of changed pixels follows the weight.
+/// A mask is stretched to the size of the image when its size differs from the image's one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mask {
+    width: u32,
+    height: u32,
+    weights: Vec<u8>,
+}
+
+impl Mask {
+    /// This method creates a mask whose weights are computed by the given function.
+    pub fn from_fn<F>(width: u32, height: u32, weight: F) -> Mask
+    where
+        F: Fn(u32, u32) -> u8,
+    {
+        let weights = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| weight(x, y))
+            .collect();
+        Mask { width, height, weights }
+    }
+
+    /// This method creates a mask from the PNG image loaded from the given file path.
+    /// The weight of each pixel is its brightness multiplied by its opacity.
+    pub fn open(path: impl AsRef<Path>) -> Result<Mask, Error> {
+        let png_glitch = PngGlitch::open(path)?;
+        Ok(Mask::from(png_glitch))
+    }
+
+    /// This method creates a mask selecting the pixels in the given region.
+    pub fn rect(width: u32, height: u32, region: Region) -> Mask {
+        Mask::from_fn(width, height, |x, y| if region.contains(x, y) { u8::MAX } else { 0 })
+    }
+
+    /// This method creates a mask selecting the pixels in the ellipse inscribed in the given region.
+    pub fn ellipse(width: u32, height: u32, region: Region) -> Mask {
+        let rx = region.width as f32 / 2.0;
+        let ry = region.height as f32 / 2.0;
+        let cx = region.x as f32 + rx;
+        let cy = region.y as f32 + ry;
+        Mask::from_fn(width, height, |x, y| {
+            let dx = (x as f32 + 0.5 - cx) / rx;
+            let dy = (y as f32 + 0.5 - cy) / ry;
+            if dx * dx + dy * dy <= 1.0 {
+                u8::MAX
+            } else {
+                0
+            }
+        })
+    }
+
+    /// This method creates a mask whose weights change linearly in the given direction.
+    pub fn gradient(width: u32, height: u32, direction: GradientDirection) -> Mask {
+        let ratio = |value: f32, max: f32| if max > 0.0 { (value / max).clamp(0.0, 1.0) } else { 1.0 };
+        Mask::from_fn(width, height, |x, y| {
+            let weight = match direction {
+                GradientDirection::Horizontal => ratio(x as f32, width as f32 - 1.0),
+                GradientDirection::Vertical => ratio(y as f32, height as f32 - 1.0),
+                GradientDirection::Radial => {
+                    let dx = x as f32 - (width as f32 - 1.0) / 2.0;
+                    let dy = y as f32 - (height as f32 - 1.0) / 2.0;
+                    let max = ((width as f32 - 1.0).powi(2) + (height as f32 - 1.0).powi(2)).sqrt() / 2.0;
+                    1.0 - ratio((dx * dx + dy * dy).sqrt(), max)
+                }
+            };
+            (weight * 255.0).round() as u8
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// This method returns the weight of the given position in the mask.
+    pub fn weight(&self, x: u32, y: u32) -> u8 {
+        if x < self.width && y < self.height {
+            self.weights[y as usize * self.width as usize + x as usize]
+        } else {
+            0
+        }
+    }
+
+    /// This method returns a mask whose weights are inverted.
+    pub fn invert(&self) -> Mask {
+        Mask::from_fn(self.width, self.height, |x, y| u8::MAX - self.weight(x, y))
+    }
+
+    /// This method returns a mask selecting the pixels selected by either of the masks.
+    pub fn union(&self, other: &Mask) -> Mask {
+        Mask::from_fn(self.width, self.height, |x, y| {
+            self.weight(x, y).max(other.weight_in(x, y, self.width, self.height))
+        })
+    }
+
+    /// This method returns a mask selecting the pixels selected by both of the masks.
+    pub fn intersect(&self, other: &Mask) -> Mask {
+        Mask::from_fn(self.width, self.height, |x, y| {
+            self.weight(x, y).min(other.weight_in(x, y, self.width, self.height))
+        })
+    }
+
+    /// This method returns the weight of the given position, stretching the mask to the given size.
+    pub(crate) fn weight_in(&self, x: u32, y: u32, width: u32, height: u32) -> u8 {
+        if width == 0 || height == 0 {
+            return 0;
+        }
+        let x = (x as u64 * self.width as u64 / width as u64) as u32;
+        let y = (y as u64 * self.height as u64 / height as u64) as u32;
+        self.weight(x, y)
+    }
+
+    /// This method returns whether the pixel at the given position is selected, stretching the mask to the given size.
+    pub(crate) fn selects(&self, x: u32, y: u32, width: u32, height: u32) -> bool {
+        let threshold = BAYER_MATRIX[(y % 4) as usize][(x % 4) as usize] * 16 + 8;
+        self.weight_in(x, y, width, height) > threshold
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rect() {
+        let mask = Mask::rect(8, 8, Region::new(2, 2, 3, 3));
+        assert!(mask.selects(2, 2, 8, 8));
+        assert!(mask.selects(4, 4, 8, 8));
+        assert!(!mask.selects(5, 4, 8, 8));
+        assert!(!mask.invert().selects(2, 2, 8, 8));
+    }
+
+    #[test]
+    fn test_stretch() {
+        let mask = Mask::rect(2, 2, Region::new(1, 0, 1, 2));
+        assert!(!mask.selects(9, 5, 20, 10));
+        assert!(mask.selects(10, 5, 20, 10));
+    }
+
+    #[test]
+    fn test_gradient_selects_proportionally() {
+        let mask = Mask::gradient(4, 4, GradientDirection::Horizontal);
+        let selected = (0..4).filter(|y| mask.selects(2, *y, 4, 4)).count();
+        assert_eq!(2, selected);
+        assert!((0..4).all(|y| !mask.selects(0, y, 4, 4)));
+        assert!((0..4).all(|y| mask.selects(3, y, 4, 4)));
+    }
+}
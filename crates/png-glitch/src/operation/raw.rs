@@ -0,0 +1,164 @@
+use crate::operation::Domain;
+use crate::{ColorType, Error, FilterType, SidecarError};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+pub trait Raw {
+    fn export_raw(&self, path: impl AsRef<Path>, domain: Domain) -> Result<(), Error>;
+    fn import_raw(&mut self, path: impl AsRef<Path>) -> Result<(), Error>;
+}
+
+/// RawLayout describes the headerless raw file exported from an image.
+/// It is written to the sidecar file in a TOML compatible format, so that it can be read and edited by hand.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawLayout {
+    pub width: u32,
+    pub height: u32,
+    pub color_type: ColorType,
+    pub bit_depth: u8,
+    /// Whether the raw file holds the filtered bytes or the unfiltered pixels.
+    pub domain: Domain,
+    /// The filter type of each scan line, which is not included in the raw file.
+    pub filter_types: Vec<FilterType>,
+}
+
+impl RawLayout {
+    /// This function returns the path of the sidecar file for the raw file, which is the path with `.toml` appended.
+    pub fn sidecar_path(path: impl AsRef<Path>) -> PathBuf {
+        let mut sidecar = OsString::from(path.as_ref().as_os_str());
+        sidecar.push(".toml");
+        PathBuf::from(sidecar)
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<RawLayout, Error> {
+        let text = std::fs::read_to_string(Self::sidecar_path(path))?;
+        Ok(text.parse()?)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        std::fs::write(Self::sidecar_path(path), self.to_string())?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for RawLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let domain = match self.domain {
+            Domain::Filtered => "filtered",
+            Domain::Unfiltered => "unfiltered",
+        };
+        let filter_types: Vec<String> = self.filter_types.iter().map(|filter_type| u8::from(*filter_type).to_string()).collect();
+        writeln!(f, "width = {}", self.width)?;
+        writeln!(f, "height = {}", self.height)?;
+        writeln!(f, "color_type = {}", u8::from(self.color_type))?;
+        writeln!(f, "bit_depth = {}", self.bit_depth)?;
+        writeln!(f, "domain = \"{}\"", domain)?;
+        writeln!(f, "filter_types = [{}]", filter_types.join(", "))
+    }
+}
+
+impl std::str::FromStr for RawLayout {
+    type Err = SidecarError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut width = None;
+        let mut height = None;
+        let mut color_type = None;
+        let mut bit_depth = None;
+        let mut domain = None;
+        let mut filter_types = None;
+        let lines = text.lines().map(str::trim).enumerate().map(|(index, line)| (index + 1, line));
+        for (line, text) in lines.filter(|(_, text)| !text.is_empty() && !text.starts_with('#')) {
+            let (key, value) = text.split_once('=').ok_or(SidecarError::InvalidLine { line })?;
+            let value = value.trim();
+            let invalid = |key| SidecarError::InvalidValue { line, key };
+            match key.trim() {
+                "width" => width = Some(value.parse().map_err(|_| invalid("width"))?),
+                "height" => height = Some(value.parse().map_err(|_| invalid("height"))?),
+                "color_type" => {
+                    let value: u8 = value.parse().map_err(|_| invalid("color_type"))?;
+                    color_type = Some(ColorType::try_from(value).map_err(|_| invalid("color_type"))?)
+                }
+                "bit_depth" => bit_depth = Some(value.parse().map_err(|_| invalid("bit_depth"))?),
+                "domain" => {
+                    domain = match value.trim_matches('"') {
+                        "filtered" => Some(Domain::Filtered),
+                        "unfiltered" => Some(Domain::Unfiltered),
+                        _ => return Err(invalid("domain")),
+                    }
+                }
+                "filter_types" => {
+                    let list = value
+                        .strip_prefix('[')
+                        .and_then(|value| value.strip_suffix(']'))
+                        .ok_or(invalid("filter_types"))?;
+                    let values = list.split(',').map(str::trim).filter(|value| !value.is_empty());
+                    let values = values.map(|value| value.parse::<u8>().map(FilterType::from).map_err(|_| invalid("filter_types")));
+                    filter_types = Some(values.collect::<Result<_, _>>()?)
+                }
+                key => return Err(SidecarError::UnknownKey { line, key: key.to_string() }),
+            }
+        }
+        Ok(RawLayout {
+            width: width.ok_or(SidecarError::MissingKey { key: "width" })?,
+            height: height.ok_or(SidecarError::MissingKey { key: "height" })?,
+            color_type: color_type.ok_or(SidecarError::MissingKey { key: "color_type" })?,
+            bit_depth: bit_depth.ok_or(SidecarError::MissingKey { key: "bit_depth" })?,
+            domain: domain.unwrap_or_default(),
+            filter_types: filter_types.ok_or(SidecarError::MissingKey { key: "filter_types" })?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn layout() -> RawLayout {
+        RawLayout {
+            width: 3,
+            height: 2,
+            color_type: ColorType::TrueColor,
+            bit_depth: 8,
+            domain: Domain::Unfiltered,
+            filter_types: vec![FilterType::Paeth, FilterType::Unknown(7)],
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = layout().to_string();
+        assert_eq!(
+            "width = 3\nheight = 2\ncolor_type = 2\nbit_depth = 8\ndomain = \"unfiltered\"\nfilter_types = [4, 7]\n",
+            text
+        );
+        assert_eq!(layout(), text.parse().unwrap());
+    }
+
+    #[test]
+    fn test_parse_edited_layout() {
+        let text = "# edited\nfilter_types = [ 1,2 ]\n\nwidth=3\nheight = 2\ncolor_type = 2\nbit_depth = 8\n";
+        let parsed: RawLayout = text.parse().unwrap();
+        assert_eq!(Domain::Filtered, parsed.domain);
+        assert_eq!(vec![FilterType::Sub, FilterType::Up], parsed.filter_types);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(Err(SidecarError::MissingKey { key: "height" }), "width = 3".parse::<RawLayout>());
+        assert_eq!(
+            Err(SidecarError::InvalidValue { line: 3, key: "color_type" }),
+            layout().to_string().replace("color_type = 2", "color_type = 5").parse::<RawLayout>()
+        );
+        assert_eq!(
+            Err(SidecarError::UnknownKey { line: 7, key: "depth".to_string() }),
+            format!("{}depth = 8\n", layout()).parse::<RawLayout>()
+        );
+        assert_eq!(Err(SidecarError::InvalidLine { line: 2 }), "# edited\nwidth 3\n".parse::<RawLayout>());
+    }
+
+    #[test]
+    fn test_sidecar_path() {
+        assert_eq!(PathBuf::from("etc/sample.raw.toml"), RawLayout::sidecar_path("etc/sample.raw"));
+    }
+}
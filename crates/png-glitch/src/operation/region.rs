@@ -0,0 +1,37 @@
+/// Region represents a rectangle in an image. All values are in pixels.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Region {
+        Region { x, y, width, height }
+    }
+
+    /// This method returns whether the given position is in the region.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        let x = x as u64;
+        let y = y as u64;
+        let left = self.x as u64;
+        let top = self.y as u64;
+        left <= x && x < left + self.width as u64 && top <= y && y < top + self.height as u64
+    }
+
+    /// This method returns the region moved to the given position.
+    pub fn moved_to(&self, x: u32, y: u32) -> Region {
+        Region { x, y, ..*self }
+    }
+
+    /// This method returns the part of the region inside an image with the given size.
+    pub fn clip(&self, width: u32, height: u32) -> Region {
+        let x = self.x.min(width);
+        let y = self.y.min(height);
+        let right = self.x.saturating_add(self.width).min(width);
+        let bottom = self.y.saturating_add(self.height).min(height);
+        Region::new(x, y, right - x, bottom - y)
+    }
+}
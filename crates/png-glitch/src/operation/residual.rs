@@ -0,0 +1,75 @@
+use crate::operation::FilterStrategy;
+
+pub trait Residual {
+    fn edit_residuals(&mut self, options: &ResidualOptions);
+}
+
+/// ResidualOperation specifies how the filtered bytes, known as residuals, are edited.
+/// Residuals are handled as signed values in the range of `-128..=127`, except for `Add`, which wraps around.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ResidualOperation {
+    Add(i16),
+    Multiply(f32),
+    Invert,
+    /// Each residual is rounded to the nearest multiple of the given step.
+    Quantize(u8),
+}
+
+impl ResidualOperation {
+    pub(crate) fn apply(&self, residual: u8) -> u8 {
+        let signed = residual as i8 as i32;
+        match *self {
+            ResidualOperation::Add(value) => (residual as i32 + value as i32).rem_euclid(256) as u8,
+            ResidualOperation::Multiply(factor) => {
+                let value = (signed as f32 * factor).round() as i32;
+                value.clamp(i8::MIN as i32, i8::MAX as i32) as i8 as u8
+            }
+            ResidualOperation::Invert => residual.wrapping_neg(),
+            ResidualOperation::Quantize(step) => {
+                let step = step.max(1) as f32;
+                let value = ((signed as f32 / step).round() * step) as i32;
+                value.clamp(i8::MIN as i32, i8::MAX as i32) as i8 as u8
+            }
+        }
+    }
+}
+
+/// ResidualOptions describes how residuals are edited.
+///
+/// The scan lines from `from` to `from + lines - 1` are filtered again with the filters chosen by `filter_strategy`
+/// before their residuals are edited. The other scan lines, including their filter types, are left as they were.
+/// Edits on Sub residuals spread along rows, while the ones on Up residuals spread along columns.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResidualOptions {
+    pub filter_strategy: FilterStrategy,
+    pub operation: ResidualOperation,
+    pub from: u32,
+    pub lines: u32,
+}
+
+impl ResidualOptions {
+    pub fn new(filter_strategy: impl Into<FilterStrategy>, operation: ResidualOperation) -> ResidualOptions {
+        ResidualOptions {
+            filter_strategy: filter_strategy.into(),
+            operation,
+            from: 0,
+            lines: u32::MAX,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_operations() {
+        assert_eq!(2, ResidualOperation::Add(4).apply(254));
+        assert_eq!(254, ResidualOperation::Add(-4).apply(2));
+        assert_eq!(127, ResidualOperation::Multiply(4.0).apply(100));
+        assert_eq!((-20i8) as u8, ResidualOperation::Multiply(2.0).apply((-10i8) as u8));
+        assert_eq!((-5i8) as u8, ResidualOperation::Invert.apply(5));
+        assert_eq!(16, ResidualOperation::Quantize(8).apply(13));
+        assert_eq!((-16i8) as u8, ResidualOperation::Quantize(8).apply((-13i8) as u8));
+    }
+}
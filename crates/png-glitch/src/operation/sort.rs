@@ -0,0 +1,167 @@
+use crate::{FilterType, Pixel};
+
+pub trait Sort {
+    fn sort(&mut self, options: &SortOptions);
+}
+
+/// SortDirection specifies the direction along which pixels are sorted.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum SortDirection {
+    /// Pixels in each row are sorted.
+    #[default]
+    Row,
+    /// Pixels in each column are sorted.
+    Column,
+}
+
+/// SortKey specifies the value of a pixel used to detect intervals and to sort pixels.
+/// Each value is in the range of `0.0..=1.0`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum SortKey {
+    #[default]
+    Brightness,
+    Hue,
+    Saturation,
+}
+
+impl SortKey {
+    pub(crate) fn evaluate(&self, [r, g, b]: [f32; 3]) -> f32 {
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+        match self {
+            SortKey::Brightness => 0.299 * r + 0.587 * g + 0.114 * b,
+            SortKey::Saturation => {
+                if max > 0.0 {
+                    delta / max
+                } else {
+                    0.0
+                }
+            }
+            SortKey::Hue => {
+                let hue = if delta == 0.0 {
+                    0.0
+                } else if max == r {
+                    ((g - b) / delta).rem_euclid(6.0)
+                } else if max == g {
+                    (b - r) / delta + 2.0
+                } else {
+                    (r - g) / delta + 4.0
+                };
+                hue / 6.0
+            }
+        }
+    }
+}
+
+/// SortOptions describes how pixels are sorted.
+///
+/// Pixels are sorted within intervals, each of which is a run of the pixels
+/// whose `interval_key` value is between `lower_threshold` and `upper_threshold` inclusive.
+/// When `filter_type` is specified, the filter is applied to all scan lines after sorting.
+/// Otherwise, each scan line is filtered again with its own filter type.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SortOptions {
+    pub direction: SortDirection,
+    pub interval_key: SortKey,
+    pub lower_threshold: f32,
+    pub upper_threshold: f32,
+    pub sort_key: SortKey,
+    pub reverse: bool,
+    pub filter_type: Option<FilterType>,
+}
+
+impl Default for SortOptions {
+    fn default() -> Self {
+        SortOptions {
+            direction: SortDirection::default(),
+            interval_key: SortKey::default(),
+            lower_threshold: 0.25,
+            upper_threshold: 0.8,
+            sort_key: SortKey::default(),
+            reverse: false,
+            filter_type: None,
+        }
+    }
+}
+
+pub fn sort_pixels<F>(pixels: &mut [Pixel], options: &SortOptions, rgb: F)
+where
+    F: Fn(&Pixel) -> [f32; 3],
+{
+    let in_interval = |pixel: &Pixel| {
+        let value = options.interval_key.evaluate(rgb(pixel));
+        options.lower_threshold <= value && value <= options.upper_threshold
+    };
+
+    let mut start = 0;
+    while start < pixels.len() {
+        if !in_interval(&pixels[start]) {
+            start += 1;
+            continue;
+        }
+        let mut end = start + 1;
+        while end < pixels.len() && in_interval(&pixels[end]) {
+            end += 1;
+        }
+        sort_interval(&mut pixels[start..end], options, &rgb);
+        start = end;
+    }
+}
+
+fn sort_interval<F>(pixels: &mut [Pixel], options: &SortOptions, rgb: F)
+where
+    F: Fn(&Pixel) -> [f32; 3],
+{
+    let mut keyed: Vec<(f32, Pixel)> = pixels
+        .iter()
+        .map(|pixel| (options.sort_key.evaluate(rgb(pixel)), *pixel))
+        .collect();
+    keyed.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    if options.reverse {
+        keyed.reverse();
+    }
+    for (pixel, (_, sorted)) in pixels.iter_mut().zip(keyed) {
+        *pixel = sorted;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gray(value: u16) -> Pixel {
+        Pixel::new(&[value])
+    }
+
+    fn rgb(pixel: &Pixel) -> [f32; 3] {
+        let value = pixel.samples()[0] as f32 / 255.0;
+        [value, value, value]
+    }
+
+    #[test]
+    fn test_sort_within_intervals() {
+        let mut pixels = vec![gray(0), gray(200), gray(100), gray(150), gray(10), gray(180), gray(120)];
+        sort_pixels(&mut pixels, &SortOptions::default(), rgb);
+        let expected = vec![gray(0), gray(100), gray(150), gray(200), gray(10), gray(120), gray(180)];
+        assert_eq!(expected, pixels);
+    }
+
+    #[test]
+    fn test_reverse() {
+        let mut pixels = vec![gray(100), gray(200), gray(150)];
+        let options = SortOptions {
+            reverse: true,
+            ..SortOptions::default()
+        };
+        sort_pixels(&mut pixels, &options, rgb);
+        assert_eq!(vec![gray(200), gray(150), gray(100)], pixels);
+    }
+
+    #[test]
+    fn test_hue() {
+        assert_eq!(0.0, SortKey::Hue.evaluate([1.0, 0.0, 0.0]));
+        assert!((SortKey::Hue.evaluate([0.0, 1.0, 0.0]) - 1.0 / 3.0).abs() < f32::EPSILON);
+        assert!((SortKey::Hue.evaluate([0.0, 0.0, 1.0]) - 2.0 / 3.0).abs() < f32::EPSILON);
+    }
+}
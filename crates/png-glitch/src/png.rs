@@ -1,23 +1,37 @@
-use crate::operation::{Encode, Scan, Transpose};
+use crate::operation::{
+    byte_ranges, nearest_neighbor, quantize, sort_pixels, to_rgb8, Axis, Bitwise, BitwiseOptions, BlockOperation, BlockOptions, ChannelShift, ChannelShiftOptions, Color,
+    Convert, ConvertOptions, DatabendOptions, Databend, Diff, Difference, Displace, DisplaceOptions, Domain, LineDifference, Encode, Encoder, Filter, FilterStrategy, Geometry, Mask, MoveBlock, PadOptions, Raw, RawLayout, Region, Residual, ResidualOptions, Rotation, Scan,
+    Sort, SortDirection, SortKey, SortOptions, Transpose,
+};
 use crate::png::parser::Header;
 use crate::png::parser::Parser;
 use crate::png::parser::Terminator;
 use crate::png::parser::{Chunk, ChunkType};
 pub use crate::png::scan_line::ScanLine;
-use anyhow::Context;
-pub use parser::ColorType;
-pub use scan_line::FilterType;
+use history::{History, Layout};
+pub use history::Snapshot;
+use idat_writer::IdatWriter;
+pub use parser::{ColorType, ParseOptions};
+pub use png_error::{Error, OperationError, PngError, SidecarError};
+pub use scan_line::{FilterType, Pixel, UnknownFilterPolicy};
+pub(crate) use scan_line::max_sample;
 use scan_line::MemoryRange;
 use std::cell::RefCell;
 use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::ops::Range;
 use std::path::Path;
 use std::rc::Rc;
 
+mod history;
+mod idat_writer;
 mod parser;
 mod png_error;
 mod scan_line;
 
+/// The number of scan lines compressed at once on encoding.
+const BAND_LINES: usize = 64;
+
 pub type DecodedData = Vec<u8>;
 pub type SharedDecodedData = Rc<RefCell<DecodedData>>;
 
@@ -30,27 +44,116 @@ pub struct Png {
     terminator: Terminator,
     misc_chunks: Vec<Chunk>,
     data: SharedDecodedData,
+    unknown_filter_policy: UnknownFilterPolicy,
+    max_decoded_size: usize,
+    history: Option<History>,
 }
 
 impl Png {
-    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
-        let mut file = File::create(path)?;
-        let _ = self.encode(&mut file)?;
+    pub fn open(path: impl AsRef<Path>, options: &ParseOptions) -> Result<Png, Error> {
+        let file = File::open(path)?;
+        Parser::read(BufReader::new(file), options)
+    }
+
+    pub fn read(reader: impl Read, options: &ParseOptions) -> Result<Png, Error> {
+        Parser::read(reader, options)
+    }
+
+    pub fn parse_buffer(buffer: Vec<u8>, options: &ParseOptions) -> Result<Png, Error> {
+        Parser::parse_buffer(buffer, options)
+    }
+
+    /// # Safety
+    ///
+    /// The file must not be modified while the returned object is alive.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn open_mmap(path: impl AsRef<Path>, options: &ParseOptions) -> Result<Png, Error> {
+        let file = File::open(path)?;
+        let mmap = memmap2::Mmap::map(&file)?;
+        Parser::parse_source(std::sync::Arc::new(mmap), options)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let file = File::create(path)?;
+        self.encode(BufWriter::new(file))?;
         Ok(())
     }
 
-    fn new(header: Header, terminator: Terminator, misc_chunks: Vec<Chunk>, data: Vec<u8>) -> Png {
+    fn new(header: Header, terminator: Terminator, misc_chunks: Vec<Chunk>, data: Vec<u8>, options: &ParseOptions) -> Png {
         let data = share_decoded_data(data);
         Png {
             header,
             terminator,
             misc_chunks,
             data,
+            unknown_filter_policy: options.unknown_filter_policy,
+            max_decoded_size: options.max_decoded_size,
+            history: None,
+        }
+    }
+
+    /// Creates a non-interlaced image from the given scan lines. Each scan line starts with its filter type.
+    /// The data is truncated or padded with zero to fit the size of the image, which is limited with `options.max_decoded_size`.
+    /// The created image keeps the options, so that the images created from it have the same limit.
+    pub fn create(
+        width: u32,
+        height: u32,
+        color_type: ColorType,
+        bit_depth: u8,
+        misc_chunks: Vec<Chunk>,
+        mut data: Vec<u8>,
+        options: &ParseOptions,
+    ) -> Result<Png, OperationError> {
+        let header = Png::create_header(width, height, color_type, bit_depth)?;
+        data.resize(Png::decoded_size(&header, options.max_decoded_size)?, 0);
+        Ok(Png::new(header, Terminator::default(), misc_chunks, data, options))
+    }
+
+    /// Returns the size of the scan lines of an image created with [Png::create], without allocating them.
+    pub fn created_size(width: u32, height: u32, color_type: ColorType, bit_depth: u8, max_decoded_size: usize) -> Result<usize, OperationError> {
+        Png::decoded_size(&Png::create_header(width, height, color_type, bit_depth)?, max_decoded_size)
+    }
+
+    /// Returns the options to create another image from this image with.
+    fn options(&self) -> ParseOptions {
+        ParseOptions {
+            max_decoded_size: self.max_decoded_size,
+            unknown_filter_policy: self.unknown_filter_policy,
+            ..ParseOptions::default()
+        }
+    }
+
+    fn create_header(width: u32, height: u32, color_type: ColorType, bit_depth: u8) -> Result<Header, OperationError> {
+        Header::create(width, height, color_type, bit_depth).map_err(|error| match error {
+            PngError::EmptyImage => OperationError::EmptyImage,
+            _ => OperationError::InvalidBitDepth,
+        })
+    }
+
+    fn decoded_size(header: &Header, max_decoded_size: usize) -> Result<usize, OperationError> {
+        (header.height() as usize)
+            .checked_mul(header.scan_line_width())
+            .filter(|size| *size <= max_decoded_size)
+            .ok_or(OperationError::TooLargeImage)
+    }
+
+    /// Copies the image without the history.
+    /// The decoded data is copied, while the chunks other than IDAT are cloned, sharing the memory mapped file if they refer to it.
+    pub fn fork(&self) -> Png {
+        Png {
+            header: self.header.clone(),
+            terminator: self.terminator.clone(),
+            misc_chunks: self.misc_chunks.clone(),
+            data: share_decoded_data(self.snapshot()),
+            unknown_filter_policy: self.unknown_filter_policy,
+            max_decoded_size: self.max_decoded_size,
+            history: None,
         }
     }
 
+    #[cfg(test)]
     fn parse(buffer: &[u8]) -> anyhow::Result<Png> {
-        let png = Parser::parse(buffer)?;
+        let png = Parser::read(buffer, &ParseOptions::default())?;
         Ok(png)
     }
 
@@ -62,6 +165,413 @@ impl Png {
         self.header.height()
     }
 
+    pub fn set_unknown_filter_policy(&mut self, policy: UnknownFilterPolicy) {
+        self.unknown_filter_policy = policy;
+    }
+
+    pub fn color_type(&self) -> ColorType {
+        self.header.color_type()
+    }
+
+    pub fn bit_depth(&self) -> u8 {
+        self.header.bit_depth()
+    }
+
+    pub fn palette(&self) -> Vec<[u8; 3]> {
+        self.chunk_data(ChunkType::PLTE)
+            .map(|data| data.chunks_exact(3).map(|rgb| [rgb[0], rgb[1], rgb[2]]).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the alpha of each palette entry in tRNS chunk. It is empty unless the image has a palette with transparency.
+    pub fn transparency(&self) -> Vec<u8> {
+        match self.color_type() {
+            ColorType::IndexColor => self.chunk_data(ChunkType::TRNS).map(|data| data.to_vec()).unwrap_or_default(),
+            _ => vec![],
+        }
+    }
+
+    fn chunk_data(&self, chunk_type: [u8; 4]) -> Option<&[u8]> {
+        self.misc_chunks
+            .iter()
+            .find(|chunk| chunk.chunk_type == ChunkType::Other(chunk_type))
+            .map(|chunk| &*chunk.data)
+    }
+
+    fn scan_lines_in(&self, data: &SharedDecodedData, from: usize, lines: usize) -> Vec<ScanLine> {
+        let lines = lines.min((self.height() as usize).saturating_sub(from));
+        let color_type = self.header.color_type();
+        let bit_depth = self.header.bit_depth();
+        let width = self.width();
+        (0..lines).flat_map(|index| {
+            let index = from + index;
+            let range = self.scan_line_range(index, 1);
+            let range = MemoryRange::new(data.clone(), range, color_type, bit_depth, width);
+            ScanLine::try_from(range)
+        })
+            .collect()
+    }
+
+    pub fn snapshot(&self) -> DecodedData {
+        self.data.borrow().clone()
+    }
+
+    /// Replaces the data with the snapshot, unless the snapshot is taken from an image in another size.
+    pub fn restore(&self, snapshot: &Snapshot) {
+        let mut data = self.data.borrow_mut();
+        if snapshot.data().len() == data.len() {
+            data.copy_from_slice(snapshot.data());
+        }
+    }
+
+    /// Starts recording the changes on the data, keeping `limit` steps at most.
+    pub fn enable_history(&mut self, limit: usize) {
+        self.history = Some(History::new(&self.data.borrow(), self.layout(), limit));
+    }
+
+    /// Stops recording the changes on the data, and returns the history recorded so far.
+    pub fn take_history(&mut self) -> Option<History> {
+        self.history.take()
+    }
+
+    /// Resumes recording the changes with the history returned by `take_history`.
+    /// The history is rebased if the image is converted or resized while it is taken.
+    pub fn resume_history(&mut self, history: Option<History>) {
+        self.history = history;
+        self.rebase_history();
+    }
+
+    /// Discards the steps of the history if they can not be applied to the data in the current layout.
+    fn rebase_history(&mut self) {
+        let layout = self.layout();
+        if let Some(history) = self.history.as_mut() {
+            let data = self.data.borrow();
+            if !history.fits(&data, layout) {
+                history.rebase(&data, layout);
+            }
+        }
+    }
+
+    /// Starts a step of the history, which records the changes on `lines` scan lines from `from` until the next commit.
+    pub fn begin(&mut self, from: usize, lines: usize) {
+        if let Some(history) = self.history.as_mut() {
+            history.begin(&self.data.borrow(), from, lines);
+        }
+    }
+
+    pub fn commit(&mut self) -> bool {
+        match self.history.as_mut() {
+            Some(history) => history.commit(&self.data.borrow()),
+            None => false,
+        }
+    }
+
+    pub fn undo(&mut self) -> bool {
+        match self.history.as_mut() {
+            Some(history) => history.undo(&mut self.data.borrow_mut()),
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        match self.history.as_mut() {
+            Some(history) => history.redo(&mut self.data.borrow_mut()),
+            None => false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.as_ref().is_some_and(|history| history.can_undo())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.as_ref().is_some_and(|history| history.can_redo())
+    }
+
+    /// Restores the pixels not selected by the mask from the snapshot.
+    /// Pixels are composited without filters, and then each scan line is filtered again with its current filter type.
+    /// Scan lines without any selected pixel are filtered with the filter type in the snapshot.
+    pub fn restore_unmasked(&self, mask: &Mask, snapshot: DecodedData) {
+        if snapshot.len() != self.data.borrow().len() {
+            return;
+        }
+        let width = self.width();
+        let height = self.height();
+        let original = share_decoded_data(snapshot);
+        let mut filter_types: Vec<FilterType> = self.scan_lines().iter().map(|line| line.filter_type()).collect();
+        let original_filter_types: Vec<FilterType> = self
+            .scan_lines_in(&original, 0, height as usize)
+            .iter()
+            .map(|line| line.filter_type())
+            .collect();
+        self.remove_filter_in(&original, self.height() as usize);
+        self.remove_filter_in(&self.data, self.height() as usize);
+
+        let originals = self.scan_lines_in(&original, 0, height as usize);
+        for (y, (scan_line, original)) in self.scan_lines().iter().zip(originals.iter()).enumerate() {
+            let selected: Vec<bool> = (0..width).map(|x| mask.selects(x, y as u32, width, height)).collect();
+            if !selected.iter().any(|selected| *selected) {
+                filter_types[y] = original_filter_types[y];
+            }
+            for (x, _) in selected.iter().enumerate().filter(|(_, selected)| !**selected) {
+                if let Some(pixel) = original.pixel(x) {
+                    scan_line.set_pixel(x, &pixel);
+                }
+            }
+        }
+        self.apply_filter_in(&self.data, 0, &filter_types);
+    }
+
+    /// Filters the scan lines from `from` to `from + lines - 1` with the filter types chosen by the strategy.
+    /// The scan lines are unfiltered in a copy of the data up to the last of them, so that the other scan lines,
+    /// including their filter types, are left as they were.
+    fn refilter(&self, strategy: &FilterStrategy, from: usize, lines: usize) -> Vec<FilterType> {
+        let from = from.min(self.height() as usize);
+        let lines = lines.min(self.height() as usize - from);
+        let unfiltered = share_decoded_data(self.data.borrow()[..self.index_of(from + lines)].to_vec());
+        self.remove_filter_in(&unfiltered, from + lines);
+        let filter_types = match strategy.plan(from, lines) {
+            Some(filter_types) => filter_types,
+            None => (from..from + lines)
+                .map(|index| self.minimum_sum_of_absolute_differences(&unfiltered, index))
+                .collect(),
+        };
+        self.apply_filter_in(&unfiltered, from, &filter_types);
+
+        let range = self.scan_line_range(from, lines as u32);
+        self.data.borrow_mut()[range.clone()].copy_from_slice(&unfiltered.borrow()[range]);
+        filter_types
+    }
+
+    /// Runs the edit on the scan lines without filters, and filters the scan lines from `from` to `from + lines - 1` again.
+    /// Filters are removed in a copy of the data, so that the edit can read any scan line, and only the scan lines
+    /// in the range are copied back. They are filtered with `filter_type` if specified, and with their own filter types otherwise.
+    /// The other scan lines, including their filter types, are left as they were.
+    fn edit_unfiltered<F>(&self, from: usize, lines: usize, filter_type: Option<FilterType>, edit: F)
+    where
+        F: FnOnce(&mut [ScanLine]),
+    {
+        let height = self.height() as usize;
+        let from = from.min(height);
+        let lines = lines.min(height - from);
+        let unfiltered = share_decoded_data(self.snapshot());
+        self.remove_filter_in(&unfiltered, height);
+        edit(&mut self.scan_lines_in(&unfiltered, 0, height));
+
+        let filter_types: Vec<FilterType> = match filter_type {
+            Some(filter_type) => vec![filter_type; lines],
+            None => self.scan_lines_from(from, lines).iter().map(|line| line.filter_type()).collect(),
+        };
+        self.apply_filter_in(&unfiltered, from, &filter_types);
+        let range = self.scan_line_range(from, lines as u32);
+        self.data.borrow_mut()[range.clone()].copy_from_slice(&unfiltered.borrow()[range]);
+    }
+
+    fn minimum_sum_of_absolute_differences(&self, data: &SharedDecodedData, index: usize) -> FilterType {
+        let width = self.scan_line_width();
+        let mut scratch = vec![0; width * 2];
+        if index > 0 {
+            scratch[..width].copy_from_slice(&data.borrow()[self.scan_line_range(index - 1, 1)]);
+        }
+        let current: Vec<u8> = data.borrow()[self.scan_line_range(index, 1)].to_vec();
+        let scratch = share_decoded_data(scratch);
+        let scan_line = |range: Range<usize>| {
+            let range = MemoryRange::new(scratch.clone(), range, self.color_type(), self.bit_depth(), self.width());
+            ScanLine::try_from(range).ok()
+        };
+        let previous = if index > 0 { scan_line(0..width) } else { None };
+
+        let candidates = [FilterType::None, FilterType::Sub, FilterType::Up, FilterType::Average, FilterType::Paeth];
+        candidates
+            .into_iter()
+            .min_by_key(|filter_type| {
+                scratch.borrow_mut()[width..].copy_from_slice(&current);
+                match scan_line(width..width * 2) {
+                    Some(mut scan_line) => {
+                        scan_line.apply_filter(*filter_type, previous.as_ref());
+                        scratch.borrow()[width + 1..]
+                            .iter()
+                            .map(|byte| (*byte as i8).unsigned_abs() as u64)
+                            .sum()
+                    }
+                    None => u64::MAX,
+                }
+            })
+            .unwrap_or(FilterType::None)
+    }
+
+    /// Removes filters from the first `lines` scan lines in the data.
+    fn remove_filter_in(&self, data: &SharedDecodedData, lines: usize) {
+        let mut previous: Option<ScanLine> = None;
+        for mut scan_line in self.scan_lines_in(data, 0, lines) {
+            scan_line.remove_filter_with(previous.as_ref(), self.unknown_filter_policy);
+            previous = Some(scan_line);
+        }
+    }
+
+    /// Filters the unfiltered scan lines starting from `from` with the given filter types.
+    /// Scan lines are filtered from the bottom, so that each line is filtered against the unfiltered previous line.
+    fn apply_filter_in(&self, data: &SharedDecodedData, from: usize, filter_types: &[FilterType]) {
+        let previous = from.saturating_sub(1);
+        let mut scan_lines = self.scan_lines_in(data, previous, from - previous + filter_types.len());
+        while scan_lines.len() > from - previous {
+            if let Some(mut scan_line) = scan_lines.pop() {
+                let filter_type = filter_types[scan_lines.len() - (from - previous)];
+                scan_line.apply_filter(filter_type, scan_lines.last());
+            }
+        }
+    }
+
+    pub fn luminance_mask(&mut self) -> Mask {
+        self.remove_filter_from(0, self.height() as usize);
+        let color_type = self.color_type();
+        let bit_depth = self.bit_depth();
+        let palette = self.palette();
+        let transparency = self.transparency();
+        let rows: Vec<Vec<Pixel>> = self.scan_lines().iter().map(|line| line.pixels()).collect();
+        Mask::from_fn(self.width(), self.height(), |x, y| {
+            match rows.get(y as usize).and_then(|row| row.get(x as usize)) {
+                Some(pixel) => {
+                    let luminance = SortKey::Brightness.evaluate(pixel.to_rgb(color_type, bit_depth, &palette));
+                    let alpha = pixel.alpha(color_type, bit_depth, &transparency);
+                    (luminance * alpha * 255.0).round() as u8
+                }
+                None => 0,
+            }
+        })
+    }
+
+    /// Returns the colors of the pixels in each scan line, without modifying the filters.
+    fn colors(&self) -> Vec<Vec<Color>> {
+        let data = share_decoded_data(self.snapshot());
+        self.remove_filter_in(&data, self.height() as usize);
+        let color_type = self.color_type();
+        let bit_depth = self.bit_depth();
+        let palette = self.palette();
+        let transparency = self.transparency();
+        self.scan_lines_in(&data, 0, self.height() as usize)
+            .iter()
+            .map(|scan_line| {
+                scan_line
+                    .pixels()
+                    .into_iter()
+                    .map(|pixel| {
+                        let [r, g, b] = pixel.to_rgb(color_type, bit_depth, &palette);
+                        [r, g, b, pixel.alpha(color_type, bit_depth, &transparency)]
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Replaces the image with another one in a different size, color type, or bit depth.
+    /// The history is rebased if the layout of the data changes, since its steps can not be applied to it.
+    fn replace(&mut self, png: Png) {
+        self.header = png.header;
+        self.misc_chunks = png.misc_chunks;
+        self.data = png.data;
+        self.rebase_history();
+    }
+
+    /// Rebuilds the image in the given size. Each pixel is taken from the position in the original image returned by `source`,
+    /// or filled with `fill` if None is returned. Pixels are moved without filters, and then
+    /// each scan line is filtered with the filter type of the original scan line returned by `line`,
+    /// or left unfiltered if None is returned.
+    fn transform<S, L>(&mut self, width: u32, height: u32, source: S, line: L, fill: Pixel) -> Result<(), Error>
+    where
+        S: Fn(u32, u32) -> Option<(u32, u32)>,
+        L: Fn(u32) -> Option<u32>,
+    {
+        let png = Png::create(width, height, self.color_type(), self.bit_depth(), self.misc_chunks.clone(), vec![], &self.options())?;
+        let data = share_decoded_data(self.snapshot());
+        self.remove_filter_in(&data, self.height() as usize);
+        let rows: Vec<Vec<Pixel>> = self
+            .scan_lines_in(&data, 0, self.height() as usize)
+            .iter()
+            .map(|scan_line| scan_line.pixels())
+            .collect();
+        for (y, scan_line) in png.scan_lines().iter().enumerate() {
+            let pixels: Vec<Pixel> = (0..width)
+                .map(|x| {
+                    source(x, y as u32)
+                        .and_then(|(x, y)| rows.get(y as usize)?.get(x as usize).copied())
+                        .unwrap_or(fill)
+                })
+                .collect();
+            scan_line.set_pixels(&pixels);
+        }
+
+        let original: Vec<FilterType> = self.scan_lines().iter().map(|scan_line| scan_line.filter_type()).collect();
+        let filter_types: Vec<FilterType> = (0..height)
+            .map(|y| line(y).and_then(|y| original.get(y as usize)).copied().unwrap_or(FilterType::None))
+            .collect();
+        png.apply_filter_in(&png.data, 0, &filter_types);
+        self.replace(png);
+        Ok(())
+    }
+
+    fn read_block(&self, scan_lines: &[ScanLine], region: &Region) -> Vec<Vec<Pixel>> {
+        let region = region.clip(self.width(), self.height());
+        let columns = region.x as usize..(region.x + region.width) as usize;
+        scan_lines
+            .iter()
+            .skip(region.y as usize)
+            .take(region.height as usize)
+            .map(|scan_line| columns.clone().flat_map(|x| scan_line.pixel(x)).collect())
+            .collect()
+    }
+
+    fn write_block(&self, scan_lines: &[ScanLine], x: u32, y: u32, block: &[Vec<Pixel>]) {
+        for (scan_line, row) in scan_lines.iter().skip(y as usize).zip(block) {
+            for (offset, pixel) in row.iter().enumerate() {
+                scan_line.set_pixel(x as usize + offset, pixel);
+            }
+        }
+    }
+
+    /// Compresses the scan lines band by band, and writes them as IDAT chunks.
+    fn encode_data(&self, writer: impl Write) -> std::io::Result<()> {
+        let band_size = self.scan_line_width() * BAND_LINES;
+        let mut compressor = fdeflate::Compressor::new(IdatWriter::new(writer))?;
+        for band in self.data.borrow().chunks(band_size) {
+            compressor.write_data(band)?;
+        }
+        compressor.finish()?.finish()?;
+        Ok(())
+    }
+
+    /// Concatenates the bytes of the scan lines without their filter type, and writes them back after editing.
+    fn edit_bytes<F>(&mut self, domain: Domain, from: u32, lines: u32, edit: F)
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        let from = (from as usize).min(self.height() as usize);
+        let lines = (lines as usize).min(self.height() as usize - from);
+        let edit_bytes = |scan_lines: &mut [ScanLine]| {
+            let mut bytes = vec![];
+            for scan_line in scan_lines.iter_mut() {
+                let _ = scan_line.read_to_end(&mut bytes);
+            }
+            edit(&mut bytes);
+            for (scan_line, chunk) in scan_lines.iter_mut().zip(bytes.chunks(self.scan_line_width() - 1)) {
+                let _ = scan_line.write_all(chunk);
+            }
+        };
+        match domain {
+            Domain::Filtered => edit_bytes(&mut self.scan_lines_from(from, lines)),
+            Domain::Unfiltered => self.edit_unfiltered(from, lines, None, |scan_lines| edit_bytes(&mut scan_lines[from..from + lines])),
+        }
+    }
+
+    fn layout(&self) -> Layout {
+        Layout {
+            scan_line_width: self.scan_line_width(),
+            width: self.width(),
+            color_type: self.color_type(),
+            bit_depth: self.bit_depth(),
+        }
+    }
+
     fn scan_line_width(&self) -> usize {
         self.header.scan_line_width()
     }
@@ -79,16 +589,19 @@ impl Png {
 
 }
 
-impl TryFrom<&Vec<u8>> for Png {
-    type Error = anyhow::Error;
-
-    fn try_from(buffer: &Vec<u8>) -> Result<Self, Self::Error> {
-        Png::parse(buffer)
+impl Clone for Png {
+    fn clone(&self) -> Self {
+        Png {
+            history: self.history.clone(),
+            ..self.fork()
+        }
     }
 }
 
 impl Transpose for Png {
     fn transpose(&mut self, src: usize, dest: usize, lines: u32) {
+        let height = self.height() as usize;
+        let lines = (lines as usize).min(height.saturating_sub(src.max(dest))) as u32;
         let src = self.scan_line_range(src, lines);
         let dest = self.scan_line_range(dest, lines);
 
@@ -104,24 +617,421 @@ impl Transpose for Png {
     }
 }
 
+impl Filter for Png {
+    fn remove_filter_from(&mut self, from: usize, lines: usize) {
+        let previous = from.saturating_sub(1);
+        let mut scan_lines = self.scan_lines_from(previous, from - previous + lines);
+        scan_lines.reverse();
+
+        let mut previous = if from > 0 { scan_lines.pop() } else { None };
+        while let Some(mut scan_line) = scan_lines.pop() {
+            scan_line.remove_filter_with(previous.as_ref(), self.unknown_filter_policy);
+            previous = Some(scan_line);
+        }
+    }
+
+    fn apply_filter_from(&mut self, filter_type: FilterType, from: usize, lines: usize) {
+        let lines = lines.min((self.height() as usize).saturating_sub(from));
+        self.apply_filter_plan(&vec![filter_type; lines], from);
+    }
+
+    fn apply_filter_plan(&mut self, filter_types: &[FilterType], from: usize) {
+        self.apply_filter_in(&self.data, from, filter_types);
+    }
+
+    fn apply_filter_strategy(&mut self, strategy: &FilterStrategy, from: usize, lines: usize) -> Vec<FilterType> {
+        self.refilter(strategy, from, lines)
+    }
+}
+
+impl Sort for Png {
+    fn sort(&mut self, options: &SortOptions) {
+        let color_type = self.color_type();
+        let bit_depth = self.bit_depth();
+        let palette = self.palette();
+        let rgb = |pixel: &Pixel| pixel.to_rgb(color_type, bit_depth, &palette);
+        let width = self.width() as usize;
+
+        self.edit_unfiltered(0, self.height() as usize, options.filter_type, |scan_lines| match options.direction {
+            SortDirection::Row => {
+                for scan_line in scan_lines.iter() {
+                    let mut pixels = scan_line.pixels();
+                    sort_pixels(&mut pixels, options, rgb);
+                    scan_line.set_pixels(&pixels);
+                }
+            }
+            SortDirection::Column => {
+                for x in 0..width {
+                    let mut pixels: Vec<Pixel> = scan_lines.iter().flat_map(|line| line.pixel(x)).collect();
+                    sort_pixels(&mut pixels, options, rgb);
+                    for (scan_line, pixel) in scan_lines.iter().zip(pixels.iter()) {
+                        scan_line.set_pixel(x, pixel);
+                    }
+                }
+            }
+        });
+    }
+}
+
+impl ChannelShift for Png {
+    fn shift_channels(&mut self, options: &ChannelShiftOptions) {
+        let width = self.width() as usize;
+        let from = options.from as usize;
+        let lines = options.lines as usize;
+
+        self.edit_unfiltered(from, lines, options.filter_type, |scan_lines| {
+            let original: Vec<Vec<Pixel>> = scan_lines.iter().map(|line| line.pixels()).collect();
+            let from = from.min(original.len());
+            let to = from.saturating_add(lines).min(original.len());
+
+            for y in from..to {
+                let mut pixels = original[y].clone();
+                for offset in options.offsets.iter() {
+                    let source_y = options.edge_mode.resolve(y as i64 - offset.y as i64, original.len());
+                    for (x, pixel) in pixels.iter_mut().enumerate() {
+                        let source_x = options.edge_mode.resolve(x as i64 - offset.x as i64, width);
+                        let sample = match (source_x, source_y) {
+                            (Some(source_x), Some(source_y)) => original[source_y]
+                                .get(source_x)
+                                .and_then(|pixel| pixel.sample(offset.channel))
+                                .unwrap_or(0),
+                            _ => 0,
+                        };
+                        pixel.set_sample(offset.channel, sample);
+                    }
+                }
+                scan_lines[y].set_pixels(&pixels);
+            }
+        });
+    }
+}
+
+impl Displace for Png {
+    fn displace(&mut self, options: &DisplaceOptions) {
+        let width = self.width() as usize;
+        let from = options.from.min(self.height()) as usize;
+        let lines = (options.lines as usize).min(self.height() as usize - from);
+        let displace = |scan_lines: &[ScanLine]| {
+            let amounts = options.displacement.amounts(from, scan_lines.len());
+            for (scan_line, amount) in scan_lines.iter().zip(amounts) {
+                let original = scan_line.pixels();
+                let zero = Pixel::new(&vec![0; scan_line.color_type().channels()]);
+                let pixels: Vec<Pixel> = (0..width)
+                    .map(|x| match options.edge_mode.resolve(x as i64 - amount, width) {
+                        Some(source) => original.get(source).copied().unwrap_or(zero),
+                        None => zero,
+                    })
+                    .collect();
+                scan_line.set_pixels(&pixels);
+            }
+        };
+        match options.domain {
+            Domain::Filtered => displace(&self.scan_lines_from(from, lines)),
+            Domain::Unfiltered => self.edit_unfiltered(from, lines, None, |scan_lines| displace(&scan_lines[from..from + lines])),
+        }
+    }
+}
+
+impl MoveBlock for Png {
+    fn move_block(&mut self, options: &BlockOptions) {
+        let move_block = |scan_lines: &[ScanLine]| {
+            let source = self.read_block(scan_lines, &options.source);
+            match options.operation {
+                BlockOperation::Swap => {
+                    let destination = self.read_block(scan_lines, &options.source.moved_to(options.x, options.y));
+                    self.write_block(scan_lines, options.x, options.y, &source);
+                    self.write_block(scan_lines, options.source.x, options.source.y, &destination);
+                }
+                operation => {
+                    for (x, y) in operation.destinations(&options.source, options.x, options.y, self.width(), self.height()) {
+                        self.write_block(scan_lines, x, y, &source);
+                    }
+                }
+            }
+        };
+        match options.domain {
+            Domain::Filtered => move_block(&self.scan_lines()),
+            Domain::Unfiltered => self.edit_unfiltered(0, self.height() as usize, None, |scan_lines| move_block(scan_lines)),
+        }
+    }
+}
+
+impl Bitwise for Png {
+    fn bitwise(&mut self, options: &BitwiseOptions) {
+        self.edit_bytes(options.domain, options.from, options.lines, |bytes| options.operation.apply(bytes));
+    }
+}
+
+impl Databend for Png {
+    fn databend(&mut self, options: &DatabendOptions) {
+        let bytes_per_pixel = self.color_type().bit_per_pixel(self.bit_depth()).div_ceil(8);
+        let bytes_per_line = self.scan_line_width() - 1;
+        self.edit_bytes(options.domain, options.from, options.lines, |bytes| {
+            options.effect.apply(bytes, bytes_per_pixel, bytes_per_line)
+        });
+    }
+}
+
+impl Convert for Png {
+    fn convert(&mut self, options: &ConvertOptions) -> Result<(), Error> {
+        let misc_chunks = self
+            .misc_chunks
+            .iter()
+            .filter(|chunk| !chunk.chunk_type.depends_on_color_type())
+            .cloned()
+            .collect();
+        let mut png = Png::create(
+            self.width(),
+            self.height(),
+            options.color_type,
+            options.bit_depth,
+            misc_chunks,
+            vec![],
+            &self.options(),
+        )?;
+        let colors = self.colors();
+        let palette = match options.color_type {
+            ColorType::IndexColor => {
+                let rgb: Vec<[u8; 3]> = colors.iter().flatten().map(|color| to_rgb8(*color)).collect();
+                quantize(&rgb, options.palette_size())
+            }
+            _ => vec![],
+        };
+        if !palette.is_empty() {
+            png.misc_chunks.insert(0, Chunk::with_crc(ChunkType::Other(ChunkType::PLTE), palette.concat()));
+        }
+
+        let mut encoder = Encoder::new(options.color_type, options.bit_depth, &palette);
+        for (scan_line, row) in png.scan_lines().iter().zip(colors) {
+            let pixels: Vec<Pixel> = row.into_iter().map(|color| encoder.encode(color)).collect();
+            scan_line.set_pixels(&pixels);
+        }
+        let filter_types: Vec<FilterType> = self.scan_lines().iter().map(|scan_line| scan_line.filter_type()).collect();
+        png.apply_filter_in(&png.data, 0, &filter_types);
+        self.replace(png);
+        Ok(())
+    }
+}
+
+impl Geometry for Png {
+    fn crop(&mut self, region: &Region) -> Result<(), Error> {
+        let region = region.clip(self.width(), self.height());
+        if region.width == 0 || region.height == 0 {
+            return Err(OperationError::RegionOutOfBounds.into());
+        }
+        self.transform(
+            region.width,
+            region.height,
+            |x, y| Some((region.x + x, region.y + y)),
+            |y| Some(region.y + y),
+            Pixel::default(),
+        )
+    }
+
+    fn pad(&mut self, options: &PadOptions) -> Result<(), Error> {
+        let original_width = self.width();
+        let original_height = self.height();
+        let width = [options.left, options.right].iter().try_fold(original_width, |width, margin| width.checked_add(*margin));
+        let height = [options.top, options.bottom].iter().try_fold(original_height, |height, margin| height.checked_add(*margin));
+        let (Some(width), Some(height)) = (width, height) else {
+            return Err(OperationError::TooLargeImage.into());
+        };
+        self.transform(
+            width,
+            height,
+            |x, y| {
+                let x = x.checked_sub(options.left).filter(|x| *x < original_width)?;
+                let y = y.checked_sub(options.top).filter(|y| *y < original_height)?;
+                Some((x, y))
+            },
+            |y| Some(y.saturating_sub(options.top).min(original_height - 1)),
+            options.pixel,
+        )
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        let original_width = self.width();
+        let original_height = self.height();
+        self.transform(
+            width,
+            height,
+            |x, y| {
+                let x = nearest_neighbor(x, width, original_width);
+                let y = nearest_neighbor(y, height, original_height);
+                Some((x, y))
+            },
+            |y| Some(nearest_neighbor(y, height, original_height)),
+            Pixel::default(),
+        )
+    }
+
+    fn rotate(&mut self, rotation: Rotation) -> Result<(), Error> {
+        let original_width = self.width();
+        let original_height = self.height();
+        let (width, height) = rotation.size(original_width, original_height);
+        self.transform(
+            width,
+            height,
+            |x, y| Some(rotation.source(original_width, original_height, x, y)),
+            |y| match rotation {
+                Rotation::HalfTurn => Some(original_height - 1 - y),
+                Rotation::Clockwise | Rotation::CounterClockwise => None,
+            },
+            Pixel::default(),
+        )
+    }
+
+    fn flip(&mut self, axis: Axis) -> Result<(), Error> {
+        let width = self.width();
+        let height = self.height();
+        match axis {
+            Axis::Horizontal => self.transform(width, height, |x, y| Some((width - 1 - x, y)), Some, Pixel::default()),
+            Axis::Vertical => self.transform(
+                width,
+                height,
+                |x, y| Some((x, height - 1 - y)),
+                |y| Some(height - 1 - y),
+                Pixel::default(),
+            ),
+        }
+    }
+}
+
+impl Raw for Png {
+    fn export_raw(&self, path: impl AsRef<Path>, domain: Domain) -> Result<(), Error> {
+        let path = path.as_ref();
+        let layout = RawLayout {
+            width: self.width(),
+            height: self.height(),
+            color_type: self.color_type(),
+            bit_depth: self.bit_depth(),
+            domain,
+            filter_types: self.scan_lines().iter().map(|scan_line| scan_line.filter_type()).collect(),
+        };
+        let data = share_decoded_data(self.snapshot());
+        if domain == Domain::Unfiltered {
+            self.remove_filter_in(&data, self.height() as usize);
+        }
+        let mut bytes = Vec::with_capacity(data.borrow().len());
+        for mut scan_line in self.scan_lines_in(&data, 0, self.height() as usize) {
+            scan_line.read_to_end(&mut bytes)?;
+        }
+        std::fs::write(path, bytes)?;
+        layout.write(path)
+    }
+
+    fn import_raw(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let layout = RawLayout::read(path)?;
+        let image = (self.width(), self.height(), self.color_type(), self.bit_depth());
+        if image != (layout.width, layout.height, layout.color_type, layout.bit_depth) {
+            return Err(OperationError::LayoutMismatch.into());
+        }
+        if layout.filter_types.len() != self.height() as usize {
+            return Err(OperationError::FilterCountMismatch.into());
+        }
+        let bytes = std::fs::read(path)?;
+
+        if layout.domain == Domain::Unfiltered {
+            self.remove_filter_from(0, self.height() as usize);
+        }
+        let mut scan_lines = self.scan_lines();
+        for (scan_line, chunk) in scan_lines.iter_mut().zip(bytes.chunks(self.scan_line_width() - 1)) {
+            let _ = scan_line.write_all(chunk);
+        }
+        match layout.domain {
+            Domain::Filtered => {
+                for (scan_line, filter_type) in scan_lines.iter_mut().zip(layout.filter_types) {
+                    scan_line.set_filter_type(filter_type);
+                }
+            }
+            Domain::Unfiltered => self.apply_filter_in(&self.data, 0, &layout.filter_types),
+        }
+        Ok(())
+    }
+}
+
+impl Residual for Png {
+    fn edit_residuals(&mut self, options: &ResidualOptions) {
+        self.refilter(&options.filter_strategy, options.from as usize, options.lines as usize);
+
+        let mut scan_lines = self.scan_lines_from(options.from as usize, options.lines as usize);
+
+        for scan_line in scan_lines.iter_mut() {
+            let mut residuals = vec![];
+            let _ = scan_line.read_to_end(&mut residuals);
+            for residual in residuals.iter_mut() {
+                *residual = options.operation.apply(*residual);
+            }
+            let _ = scan_line.write_all(&residuals);
+        }
+    }
+}
+
+impl Diff for Png {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        if self.width() != other.width()
+            || self.height() != other.height()
+            || self.color_type() != other.color_type()
+            || self.bit_depth() != other.bit_depth()
+        {
+            return None;
+        }
+        let width = self.width();
+        let height = self.height() as usize;
+        let max = ((1u32 << self.bit_depth()) - 1) as f32;
+        let unfiltered = share_decoded_data(self.snapshot());
+        let other_unfiltered = share_decoded_data(other.snapshot());
+        self.remove_filter_in(&unfiltered, self.height() as usize);
+        other.remove_filter_in(&other_unfiltered, other.height() as usize);
+
+        let mut lines = vec![];
+        let mut magnitudes = Vec::with_capacity(width as usize * height);
+        let filter_types = self.scan_lines().into_iter().zip(other.scan_lines()).map(|(a, b)| (a.filter_type(), b.filter_type()));
+        let scan_lines = self
+            .scan_lines_in(&unfiltered, 0, height)
+            .into_iter()
+            .zip(self.scan_lines_in(&other_unfiltered, 0, height));
+        for (index, (filter_types, (a, b))) in filter_types.zip(scan_lines).enumerate() {
+            let range = self.scan_line_range(index, 1);
+            let range = range.start + 1..range.end;
+            let difference = LineDifference {
+                index: index as u32,
+                filter_types,
+                filtered: byte_ranges(&self.data.borrow()[range.clone()], &other.data.borrow()[range.clone()]),
+                unfiltered: byte_ranges(&unfiltered.borrow()[range.clone()], &other_unfiltered.borrow()[range]),
+            };
+            magnitudes.extend(a.pixels().iter().zip(b.pixels().iter()).map(|(a, b)| {
+                let difference = a
+                    .samples()
+                    .iter()
+                    .zip(b.samples())
+                    .map(|(a, b)| a.abs_diff(*b))
+                    .max()
+                    .unwrap_or(0);
+                (difference as f32 / max * 255.0).round() as u8
+            }));
+            if difference.filter_types.0 != difference.filter_types.1
+                || !difference.filtered.is_empty()
+                || !difference.unfiltered.is_empty()
+            {
+                lines.push(difference);
+            }
+        }
+        Some(Difference::new(width, height as u32, lines, magnitudes, self.max_decoded_size))
+    }
+}
 
 impl Encode for Png {
-    fn encode(&self, mut writer: impl std::io::Write) -> anyhow::Result<()> {
+    fn encode(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
         writer.write_all(SIGNATURE)?;
-        self.header
-            .encode(&mut writer)
-            .context("Failed to encode IHDR")?;
+        self.header.encode(&mut writer)?;
         for chunk in self.misc_chunks.iter() {
             chunk.encode(&mut writer)?;
         }
-        let idat_chunk_list =
-            create_idat_chunk(self).context("Failed to create IDAT chunk list")?;
-        for chunk in idat_chunk_list.iter() {
-            chunk.encode(&mut writer).context("Failed to encode IDAT")?;
-        }
+        self.encode_data(&mut writer)?;
         self.terminator.encode(&mut writer)?;
-        writer.flush()?;
-        Ok(())
+        writer.flush()
     }
 }
 
@@ -140,44 +1050,17 @@ impl Scan for Png {
     }
 
     fn scan_lines_from(&self, from: usize, lines: usize) -> Vec<ScanLine> {
-        let color_type = self.header.color_type();
-        let bit_depth = self.header.bit_depth();
-        (0..lines).map(|index| {
-            let index = from + index;
-            let range = self.scan_line_range(index, 1);
-            let range = MemoryRange::new(self.data.clone(), range, color_type, bit_depth);
-            ScanLine::try_from(range)
-        })
-            .filter(|r| r.is_ok())
-            .map(|r| r.unwrap())
-            .collect()
+        self.scan_lines_in(&self.data, from, lines)
     }
 }
 
-fn create_idat_chunk(png: &Png) -> anyhow::Result<Vec<Chunk>> {
-    let mut list = vec![];
-
-    let mut encoder = fdeflate::Compressor::new(vec![])?;
-    encoder.write_data(&png.data.borrow())?;
-    let buffer = encoder.finish()?;
-
-    let mut crc = crc32fast::Hasher::new();
-    crc.update(ChunkType::IDAT);
-    crc.update(&buffer);
-    let crc = crc.finalize().to_be_bytes();
-
-    let chunk = Chunk::new(ChunkType::Data, buffer, crc);
-
-    list.push(chunk);
-    Ok(list)
-}
-
-
-pub const SIGNATURE: &'static [u8] = &[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+pub const SIGNATURE: &[u8] = &[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::operation::{BitOperation, ChannelOffset, Displacement, ResidualOperation};
+    use proptest::prelude::*;
 
     #[test]
     fn test_encode_ihdr() -> anyhow::Result<()> {
@@ -190,7 +1073,7 @@ mod test {
             &(png.header.inner.length() as u32).to_be_bytes()
         );
         assert_eq!(&buffer[4..8], ChunkType::IHDR);
-        assert_eq!(&buffer[8..21], &png.header.inner.data);
+        assert_eq!(&buffer[8..21], &*png.header.inner.data);
         assert_eq!(&buffer[21..25], &png.header.inner.crc);
         Ok(())
     }
@@ -211,4 +1094,543 @@ mod test {
         }
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_read_byte_by_byte() -> anyhow::Result<()> {
+        struct ByteReader<'a>(&'a [u8]);
+        impl Read for ByteReader<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let size = buf.len().min(self.0.len()).min(1);
+                buf[..size].copy_from_slice(&self.0[..size]);
+                self.0 = &self.0[size..];
+                Ok(size)
+            }
+        }
+
+        let bytes = include_bytes!("../etc/sample00.png");
+        let png = Png::parse(bytes)?;
+        let another = Png::read(ByteReader(bytes), &ParseOptions::default())?;
+        assert_eq!(*png.data.borrow(), *another.data.borrow());
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_lines_with_unknown_filter_type() -> anyhow::Result<()> {
+        let bytes = include_bytes!("../etc/sample00.png");
+        let png = Png::parse(bytes)?;
+        png.foreach_scanline(|scan_line| scan_line.set_filter_type(FilterType::Unknown(42)));
+
+        let scan_lines = png.scan_lines();
+        assert_eq!(png.height() as usize, scan_lines.len());
+        for scan_line in scan_lines {
+            assert_eq!(FilterType::Unknown(42), scan_line.filter_type());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_displace_past_height() -> anyhow::Result<()> {
+        let bytes = include_bytes!("../etc/none.png");
+        let mut png = Png::parse(bytes)?;
+        let before = png.snapshot();
+        let mut options = DisplaceOptions::new(Displacement::Noise { amplitude: 4.0, scale: 1.0, seed: 1 });
+        options.from = u32::MAX;
+        png.displace(&options);
+        assert_eq!(before, png.snapshot());
+        Ok(())
+    }
+
+    #[test]
+    fn test_create() -> anyhow::Result<()> {
+        let data = vec![0, 1, 2, 3, 4, 5, 6, 1, 7, 8, 9, 10, 11, 12];
+        let png = Png::create(2, 2, ColorType::TrueColor, 8, vec![], data.clone(), &ParseOptions::default())?;
+        let mut encoded = vec![];
+        png.encode(&mut encoded)?;
+        let parsed = Png::parse(&encoded)?;
+        assert_eq!(2, parsed.width());
+        assert_eq!(2, parsed.height());
+        assert_eq!(ColorType::TrueColor, parsed.color_type());
+        assert_eq!(data, parsed.snapshot());
+
+        assert_eq!(
+            Some(OperationError::InvalidBitDepth),
+            Png::create(2, 2, ColorType::TrueColor, 4, vec![], vec![], &ParseOptions::default()).err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert() -> anyhow::Result<()> {
+        let data = vec![0, 255, 0, 0, 0, 0, 255, 0, 255, 0, 0, 0, 0, 0];
+        let mut png = Png::create(2, 2, ColorType::TrueColor, 8, vec![], data, &ParseOptions::default())?;
+        png.enable_history(10);
+        png.begin(0, 1);
+        png.apply_filter_from(FilterType::Sub, 0, 1);
+        assert!(png.commit());
+        let original = png.snapshot();
+
+        png.convert(&ConvertOptions::new(ColorType::IndexColor, 2))?;
+        assert_eq!(vec![[0, 0, 0], [0, 0, 255], [255, 0, 0]], png.palette());
+        assert_eq!(vec![1, 0b10010000, 0, 0b10000000], png.snapshot());
+        assert!(!png.can_undo());
+
+        png.convert(&ConvertOptions::new(ColorType::TrueColor, 8))?;
+        assert_eq!(original, png.snapshot());
+        assert!(png.palette().is_empty());
+
+        png.convert(&ConvertOptions::new(ColorType::GrayScaleAlpha, 16))?;
+        let pixels = png.scan_lines()[1].pixels();
+        assert_eq!(vec![Pixel::new(&[19595, 65535]), Pixel::new(&[0, 65535])], pixels);
+        assert!(matches!(
+            png.convert(&ConvertOptions::new(ColorType::TrueColor, 4)),
+            Err(Error::Operation(OperationError::InvalidBitDepth))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_palette_with_transparency() -> anyhow::Result<()> {
+        let misc_chunks = vec![
+            Chunk::with_crc(ChunkType::Other(ChunkType::PLTE), vec![255, 0, 0, 0, 0, 255]),
+            Chunk::with_crc(ChunkType::Other(ChunkType::TRNS), vec![0]),
+        ];
+        let mut png = Png::create(2, 1, ColorType::IndexColor, 8, misc_chunks, vec![0, 0, 1], &ParseOptions::default())?;
+        png.convert(&ConvertOptions::new(ColorType::TrueColorAlpha, 8))?;
+        assert_eq!(vec![0, 255, 0, 0, 0, 0, 0, 255, 255], png.snapshot());
+        assert!(png.misc_chunks.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotate_and_flip() -> anyhow::Result<()> {
+        let original = vec![0, 1, 2, 3, 0, 4, 5, 6];
+        let mut png = Png::create(3, 2, ColorType::GrayScale, 8, vec![], original.clone(), &ParseOptions::default())?;
+        png.enable_history(10);
+        png.rotate(Rotation::Clockwise)?;
+        assert_eq!((2, 3), (png.width(), png.height()));
+        assert_eq!(vec![0, 4, 1, 0, 5, 2, 0, 6, 3], png.snapshot());
+        assert!(!png.can_undo());
+        png.rotate(Rotation::CounterClockwise)?;
+        assert_eq!(original, png.snapshot());
+
+        png.begin(0, 2);
+        png.flip(Axis::Horizontal)?;
+        assert_eq!(vec![0, 3, 2, 1, 0, 6, 5, 4], png.snapshot());
+        assert!(png.commit());
+        assert!(png.undo());
+
+        png.apply_filter_from(FilterType::Sub, 1, 1);
+        png.flip(Axis::Vertical)?;
+        assert_eq!(vec![1, 4, 1, 1, 0, 1, 2, 3], png.snapshot());
+        png.rotate(Rotation::HalfTurn)?;
+        assert_eq!(vec![0, 3, 2, 1, 1, 6, 255, 255], png.snapshot());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resize_pad_and_crop() -> anyhow::Result<()> {
+        let mut png = Png::create(3, 2, ColorType::GrayScale, 8, vec![], vec![0, 1, 2, 3, 0, 4, 5, 6], &ParseOptions::default())?;
+        png.enable_history(10);
+        png.begin(0, 1);
+        png.apply_filter_from(FilterType::Sub, 0, 1);
+        assert!(png.commit());
+        png.undo();
+        let history = png.take_history();
+        png.resize(6, 1)?;
+        png.resume_history(history);
+        assert!(!png.can_undo() && !png.can_redo());
+        assert_eq!(vec![0, 4, 4, 5, 5, 6, 6], png.snapshot());
+
+        png.pad(&PadOptions {
+            left: 1,
+            pixel: Pixel::new(&[9]),
+            ..PadOptions::default()
+        })?;
+        assert_eq!(vec![0, 9, 4, 4, 5, 5, 6, 6], png.snapshot());
+
+        png.crop(&Region::new(2, 0, 3, 10))?;
+        assert_eq!(vec![0, 4, 5, 5], png.snapshot());
+        assert!(matches!(
+            png.crop(&Region::new(10, 0, 1, 1)),
+            Err(Error::Operation(OperationError::RegionOutOfBounds))
+        ));
+        assert!(matches!(png.resize(0, 1), Err(Error::Operation(OperationError::EmptyImage))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_decoded_size_on_creation() -> anyhow::Result<()> {
+        let bytes = include_bytes!("../etc/none.png");
+        let size = Png::parse(bytes)?.data.borrow().len();
+        let options = ParseOptions { max_decoded_size: size, ..ParseOptions::default() };
+        let mut png = Png::read(bytes.as_slice(), &options)?;
+        png.rotate(Rotation::HalfTurn)?;
+        assert!(matches!(png.pad(&PadOptions::new(1)), Err(Error::Operation(OperationError::TooLargeImage))));
+        let converted = png.convert(&ConvertOptions::new(ColorType::TrueColorAlpha, 16));
+        assert!(matches!(converted, Err(Error::Operation(OperationError::TooLargeImage))));
+
+        let options = ParseOptions { max_decoded_size: size * 4, ..ParseOptions::default() };
+        let mut png = Png::read(bytes.as_slice(), &options)?;
+        png.pad(&PadOptions::new(1))?;
+        png.convert(&ConvertOptions::new(ColorType::TrueColorAlpha, 16))?;
+
+        let gigapixel = (40000, 30000, ColorType::TrueColor, 8);
+        assert!(Png::created_size(gigapixel.0, gigapixel.1, gigapixel.2, gigapixel.3, ParseOptions::default().max_decoded_size).is_err());
+        assert!(Png::created_size(gigapixel.0, gigapixel.1, gigapixel.2, gigapixel.3, 1 << 32).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_raw_round_trip() -> anyhow::Result<()> {
+        let bytes = include_bytes!("../etc/sample00.png");
+        let path = std::env::temp_dir().join(format!("png-glitch-{}.raw", std::process::id()));
+        for domain in [Domain::Filtered, Domain::Unfiltered] {
+            let mut png = Png::parse(bytes)?;
+            png.apply_filter_from(FilterType::Paeth, 0, 10);
+            let original = png.snapshot();
+            png.export_raw(&path, domain)?;
+            let raw = std::fs::read(&path)?;
+            assert_eq!(png.height() as usize * (png.scan_line_width() - 1), raw.len());
+
+            png.foreach_scanline(|scan_line| scan_line.set_filter_type(FilterType::Unknown(42)));
+            png.import_raw(&path)?;
+            assert_eq!(original, png.snapshot());
+        }
+
+        let mut another = Png::create(2, 2, ColorType::TrueColor, 8, vec![], vec![], &ParseOptions::default())?;
+        assert!(matches!(another.import_raw(&path), Err(Error::Operation(OperationError::LayoutMismatch))));
+        let sidecar = RawLayout::sidecar_path(&path);
+        let text = std::fs::read_to_string(&sidecar)?;
+        let mut png = Png::parse(bytes)?;
+        std::fs::write(&sidecar, text.replace("filter_types = [", "filter_types = [0, "))?;
+        assert!(matches!(png.import_raw(&path), Err(Error::Operation(OperationError::FilterCountMismatch))));
+        std::fs::write(&sidecar, text.replace("height", "rows"))?;
+        assert!(matches!(png.import_raw(&path), Err(Error::Sidecar(SidecarError::UnknownKey { line: 2, .. }))));
+        std::fs::remove_file(sidecar)?;
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff() -> anyhow::Result<()> {
+        let mut png = Png::open("etc/none.png", &ParseOptions::default())?;
+        png.apply_filter_from(FilterType::Paeth, 0, png.height() as usize);
+        let glitched = png.clone();
+        let index = glitched.index_of(10) + 1 + 100;
+        glitched.data.borrow_mut()[index] ^= 0xFF;
+
+        let difference = png.diff(&glitched).expect("The images should be compared");
+        let first = &difference.lines()[0];
+        assert_eq!(10, first.index);
+        assert_eq!(vec![100..101], first.filtered);
+        assert_eq!(100, first.unfiltered[0].start);
+        assert_eq!(1, difference.filtered_bytes());
+        assert!(difference.unfiltered_bytes() > 1);
+        assert!(difference.lines()[1..].iter().all(|line| line.filtered.is_empty()));
+        assert_eq!(Some(0), difference.magnitude(0, 0));
+
+        assert!(png.diff(&png.clone()).is_some_and(|difference| difference.is_empty()));
+        assert!(png.diff(&Png::open("etc/sample00.png", &ParseOptions::default())?).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_clone_copies_data() -> anyhow::Result<()> {
+        let png = Png::open("etc/sample00.png", &ParseOptions::default())?;
+        let clone = png.clone();
+        clone.data.borrow_mut()[1] ^= 0xFF;
+        assert_ne!(png.snapshot(), clone.snapshot());
+        clone.data.borrow_mut()[1] ^= 0xFF;
+        assert_eq!(png.snapshot(), clone.snapshot());
+
+        let mut encoded = vec![];
+        let mut encoded_clone = vec![];
+        png.encode(&mut encoded)?;
+        clone.encode(&mut encoded_clone)?;
+        assert_eq!(encoded, encoded_clone);
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_unmasked() -> anyhow::Result<()> {
+        let bytes = include_bytes!("../etc/paeth.png");
+        let mut original = Png::parse(bytes)?;
+        original.remove_filter_from(0, original.height() as usize);
+
+        let mut png = Png::parse(bytes)?;
+        let region = Region::new(10, 20, 30, 40);
+        let mask = Mask::rect(png.width(), png.height(), region);
+        let snapshot = png.snapshot();
+        png.sort(&SortOptions::default());
+        png.restore_unmasked(&mask, snapshot);
+        png.remove_filter_from(0, png.height() as usize);
+
+        for (y, (line, original_line)) in png.scan_lines().iter().zip(original.scan_lines().iter()).enumerate() {
+            for x in 0..png.width() {
+                if !region.contains(x, y as u32) {
+                    assert_eq!(original_line.pixel(x as usize), line.pixel(x as usize));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_filter_strategy() -> anyhow::Result<()> {
+        let bytes = include_bytes!("../etc/none.png");
+        let original = Png::parse(bytes)?;
+
+        let mut png = Png::parse(bytes)?;
+        let strategy = FilterStrategy::MinimumSumOfAbsoluteDifferences;
+        let filter_types = png.apply_filter_strategy(&strategy, 0, png.height() as usize);
+        assert_eq!(png.height() as usize, filter_types.len());
+        for (filter_type, line) in filter_types.iter().zip(png.scan_lines()) {
+            assert_eq!(*filter_type, line.filter_type());
+        }
+
+        png.remove_filter_from(0, png.height() as usize);
+        assert_eq!(*original.data.borrow(), *png.data.borrow());
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_filter_strategy_keeps_other_lines() -> anyhow::Result<()> {
+        let mut png = Png::parse(include_bytes!("../etc/sample00.png"))?;
+        let mut unfiltered = png.fork();
+        unfiltered.remove_filter_from(0, png.height() as usize);
+        let before = png.snapshot();
+
+        let filter_types = png.apply_filter_strategy(&FilterStrategy::from(FilterType::Sub), 10, 5);
+        assert_eq!(vec![FilterType::Sub; 5], filter_types);
+        let after = png.snapshot();
+        let (start, end) = (png.index_of(10), png.index_of(15));
+        assert_eq!(before[..start], after[..start]);
+        assert_eq!(before[end..], after[end..]);
+        assert!(png.scan_lines_from(10, 5).iter().all(|line| line.filter_type() == FilterType::Sub));
+
+        png.remove_filter_from(0, png.height() as usize);
+        assert_eq!(*unfiltered.data.borrow(), *png.data.borrow());
+        Ok(())
+    }
+
+    fn filter_types(png: &Png) -> Vec<FilterType> {
+        png.scan_lines().iter().map(|line| line.filter_type()).collect()
+    }
+
+    fn unfiltered_pixels(png: &Png) -> Vec<Vec<Pixel>> {
+        let png = png.fork();
+        png.remove_filter_in(&png.data, png.height() as usize);
+        png.scan_lines().iter().map(|line| line.pixels()).collect()
+    }
+
+    #[test]
+    fn test_sort_keeps_filter_types() -> anyhow::Result<()> {
+        let mut png = Png::parse(include_bytes!("../etc/sample00.png"))?;
+        let original = filter_types(&png);
+        let mut expected = png.fork();
+        expected.remove_filter_from(0, png.height() as usize);
+        let options = SortOptions::default();
+        expected.sort(&options);
+
+        png.sort(&options);
+        assert_eq!(original, filter_types(&png));
+        assert_eq!(unfiltered_pixels(&expected), unfiltered_pixels(&png));
+
+        png.sort(&SortOptions {
+            filter_type: Some(FilterType::Up),
+            ..options
+        });
+        assert!(filter_types(&png).iter().all(|filter_type| *filter_type == FilterType::Up));
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_channels_keeps_other_lines() -> anyhow::Result<()> {
+        let mut png = Png::parse(include_bytes!("../etc/sample00.png"))?;
+        let original = filter_types(&png);
+        let before = png.snapshot();
+        let mut expected = png.fork();
+        expected.remove_filter_from(0, png.height() as usize);
+        let options = ChannelShiftOptions {
+            from: 10,
+            lines: 5,
+            ..ChannelShiftOptions::new(vec![ChannelOffset::new(0, 3, 20)])
+        };
+        expected.shift_channels(&options);
+
+        png.shift_channels(&options);
+        let after = png.snapshot();
+        let (start, end) = (png.index_of(10), png.index_of(15));
+        assert_eq!(before[..start], after[..start]);
+        assert_eq!(before[end..], after[end..]);
+        assert_ne!(before[start..end], after[start..end]);
+        assert_eq!(original, filter_types(&png));
+        assert_eq!(unfiltered_pixels(&expected)[..15], unfiltered_pixels(&png)[..15]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_displace_unfiltered_keeps_other_lines() -> anyhow::Result<()> {
+        let mut png = Png::parse(include_bytes!("../etc/sample00.png"))?;
+        let original = filter_types(&png);
+        let before = png.snapshot();
+        let options = DisplaceOptions {
+            domain: Domain::Unfiltered,
+            from: 10,
+            lines: 5,
+            ..DisplaceOptions::new(Displacement::Constant(7))
+        };
+        png.displace(&options);
+
+        let after = png.snapshot();
+        let (start, end) = (png.index_of(10), png.index_of(15));
+        assert_eq!(before[..start], after[..start]);
+        assert_eq!(before[end..], after[end..]);
+        assert_eq!(original, filter_types(&png));
+
+        let mut pixels = unfiltered_pixels(&Png::parse(include_bytes!("../etc/sample00.png"))?);
+        for line in pixels[10..15].iter_mut() {
+            line.rotate_right(7);
+        }
+        let displaced = unfiltered_pixels(&png);
+        assert_eq!(pixels[10..15], displaced[10..15]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_block_unfiltered_keeps_filter_types() -> anyhow::Result<()> {
+        let mut png = Png::parse(include_bytes!("../etc/sample00.png"))?;
+        let original = filter_types(&png);
+        let mut pixels = unfiltered_pixels(&png);
+        png.move_block(&BlockOptions {
+            domain: Domain::Unfiltered,
+            ..BlockOptions::new(BlockOperation::Copy, Region::new(0, 0, 4, 2), 10, 20)
+        });
+        assert_eq!(original, filter_types(&png));
+
+        for y in 0..2 {
+            let row: Vec<Pixel> = pixels[y][0..4].to_vec();
+            pixels[20 + y][10..14].copy_from_slice(&row);
+        }
+        assert_eq!(pixels, unfiltered_pixels(&png));
+        Ok(())
+    }
+
+    #[test]
+    fn test_edit_unfiltered_bytes_keeps_other_lines() -> anyhow::Result<()> {
+        let mut png = Png::parse(include_bytes!("../etc/sample00.png"))?;
+        let original = filter_types(&png);
+        let before = png.snapshot();
+        let mut expected = png.fork();
+        expected.remove_filter_from(0, png.height() as usize);
+        let options = BitwiseOptions {
+            domain: Domain::Unfiltered,
+            from: 10,
+            lines: 5,
+            ..BitwiseOptions::new(BitOperation::Xor(0xFF))
+        };
+        expected.bitwise(&options);
+
+        png.bitwise(&options);
+        let after = png.snapshot();
+        let (start, end) = (png.index_of(10), png.index_of(15));
+        assert_eq!(before[..start], after[..start]);
+        assert_eq!(before[end..], after[end..]);
+        assert_eq!(original, filter_types(&png));
+        assert_eq!(unfiltered_pixels(&expected)[..15], unfiltered_pixels(&png)[..15]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_edit_residuals_keeps_other_lines() -> anyhow::Result<()> {
+        let mut png = Png::parse(include_bytes!("../etc/sample00.png"))?;
+        let mut original = filter_types(&png);
+        let before = png.snapshot();
+        png.edit_residuals(&ResidualOptions {
+            from: 10,
+            lines: 5,
+            ..ResidualOptions::new(FilterType::Sub, ResidualOperation::Add(1))
+        });
+
+        let after = png.snapshot();
+        let (start, end) = (png.index_of(10), png.index_of(15));
+        assert_eq!(before[..start], after[..start]);
+        assert_eq!(before[end..], after[end..]);
+        original[10..15].fill(FilterType::Sub);
+        assert_eq!(original, filter_types(&png));
+        Ok(())
+    }
+
+    #[test]
+    fn test_quarter_turn_resets_filter_types() -> anyhow::Result<()> {
+        let mut png = Png::parse(include_bytes!("../etc/sample00.png"))?;
+        let original = filter_types(&png);
+        let pixels = unfiltered_pixels(&png);
+        png.rotate(Rotation::Clockwise)?;
+        assert!(filter_types(&png).iter().all(|filter_type| *filter_type == FilterType::None));
+
+        png.rotate(Rotation::CounterClockwise)?;
+        assert_eq!(pixels, unfiltered_pixels(&png));
+        png.rotate(Rotation::HalfTurn)?;
+        png.rotate(Rotation::HalfTurn)?;
+        assert_eq!(vec![FilterType::None; original.len()], filter_types(&png));
+        Ok(())
+    }
+
+    const SAMPLES: [&[u8]; 7] = [
+        include_bytes!("../etc/average.png"),
+        include_bytes!("../etc/none.png"),
+        include_bytes!("../etc/paeth.png"),
+        include_bytes!("../etc/sample00-glitched.png"),
+        include_bytes!("../etc/sample00.png"),
+        include_bytes!("../etc/sub.png"),
+        include_bytes!("../etc/up.png"),
+    ];
+
+    fn filter_type() -> impl Strategy<Value = FilterType> {
+        (0u8..5).prop_map(FilterType::from)
+    }
+
+    #[test]
+    fn test_apply_filter_from_filters_first_line() -> anyhow::Result<()> {
+        let bytes = include_bytes!("../etc/none.png");
+        let mut png = Png::parse(bytes)?;
+        png.apply_filter_from(FilterType::Sub, 5, 3);
+        let filter_types: Vec<FilterType> = png.scan_lines().iter().map(|line| line.filter_type()).collect();
+        assert_eq!(FilterType::None, filter_types[4]);
+        assert_eq!(vec![FilterType::Sub; 3], filter_types[5..8]);
+        assert_eq!(FilterType::None, filter_types[8]);
+        Ok(())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn test_apply_filter_plan_round_trip(
+            sample in 0..SAMPLES.len(),
+            from in 0usize..512,
+            plan in prop::collection::vec(filter_type(), 0..512),
+        ) {
+            let mut png = Png::parse(SAMPLES[sample]).unwrap();
+            png.remove_filter_from(0, png.height() as usize);
+            let unfiltered = png.snapshot();
+
+            png.apply_filter_plan(&plan, from);
+            let lines = plan.len().min((png.height() as usize).saturating_sub(from));
+            let scan_lines = png.scan_lines_from(from, lines);
+            for (filter_type, scan_line) in plan.iter().zip(scan_lines.iter()) {
+                prop_assert_eq!(*filter_type, scan_line.filter_type());
+            }
+
+            let mut buffer = vec![];
+            png.encode(&mut buffer).unwrap();
+            let mut decoded = Png::parse(&buffer).unwrap();
+            decoded.remove_filter_from(0, decoded.height() as usize);
+            prop_assert!(unfiltered == decoded.snapshot());
+
+            png.remove_filter_from(from, plan.len());
+            prop_assert!(unfiltered == png.snapshot());
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use crate::png::{ColorType, DecodedData};
+use std::collections::VecDeque;
+use std::ops::Range;
+
+/// Snapshot keeps a copy of the decoded data, which can be restored later.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    data: DecodedData,
+}
+
+impl Snapshot {
+    pub(crate) fn new(data: DecodedData) -> Snapshot {
+        Snapshot { data }
+    }
+
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Layout describes how the data is sliced into scan lines and pixels.
+/// Steps recorded on the data in a layout can not be applied to the data in another one.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Layout {
+    pub scan_line_width: usize,
+    pub width: u32,
+    pub color_type: ColorType,
+    pub bit_depth: u8,
+}
+
+/// History records the changes on the decoded data as steps, which can be undone and redone.
+/// A step starts by copying the scan lines it may change, and keeps only the ranges of them changed when it ends,
+/// with their bytes before and after the change. No other copy of the data is kept.
+#[derive(Clone)]
+pub struct History {
+    size: usize,
+    layout: Layout,
+    limit: usize,
+    pending: Option<Pending>,
+    undo: VecDeque<Step>,
+    redo: Vec<Step>,
+}
+
+type Step = Vec<Change>;
+
+#[derive(Clone, Debug, PartialEq)]
+struct Change {
+    start: usize,
+    before: Vec<u8>,
+    after: Vec<u8>,
+}
+
+impl Change {
+    fn range(&self) -> Range<usize> {
+        self.start..self.start + self.before.len()
+    }
+}
+
+/// Pending keeps the scan lines copied at the start of the current step.
+#[derive(Clone)]
+struct Pending {
+    start: usize,
+    before: Vec<u8>,
+}
+
+impl History {
+    /// Creates a history keeping `limit` steps at most.
+    pub fn new(data: &[u8], layout: Layout, limit: usize) -> History {
+        History {
+            size: data.len(),
+            layout,
+            limit,
+            pending: None,
+            undo: VecDeque::new(),
+            redo: vec![],
+        }
+    }
+
+    /// Returns true if the steps can be applied to the data in the layout.
+    pub fn fits(&self, data: &[u8], layout: Layout) -> bool {
+        self.size == data.len() && self.layout == layout
+    }
+
+    /// Discards all steps, and starts recording on the data in another layout.
+    pub fn rebase(&mut self, data: &[u8], layout: Layout) {
+        *self = History::new(data, layout, self.limit);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Starts a step recording the changes on `lines` scan lines from `from`, by copying them.
+    /// The current step is committed before starting the new one.
+    pub fn begin(&mut self, data: &[u8], from: usize, lines: usize) {
+        self.commit(data);
+        let width = self.layout.scan_line_width.max(1);
+        let start = from.saturating_mul(width).min(data.len());
+        let end = from.saturating_add(lines).saturating_mul(width).min(data.len());
+        self.pending = Some(Pending {
+            start,
+            before: data[start..end].to_vec(),
+        });
+    }
+
+    /// Ends the current step, and records it if anything is changed in it. The steps to redo are cleared then.
+    /// Returns false if no step is started or nothing is changed.
+    pub fn commit(&mut self, data: &[u8]) -> bool {
+        let Some(pending) = self.pending.take() else {
+            return false;
+        };
+        let changes = self.changes(&pending, data);
+        if changes.is_empty() {
+            return false;
+        }
+        self.push(changes);
+        self.redo.clear();
+        true
+    }
+
+    /// Reverts the last step. The current step is committed before reverting.
+    pub fn undo(&mut self, data: &mut [u8]) -> bool {
+        self.commit(data);
+        let Some(step) = self.undo.pop_back() else {
+            return false;
+        };
+        for change in step.iter() {
+            data[change.range()].copy_from_slice(&change.before);
+        }
+        self.redo.push(step);
+        true
+    }
+
+    /// Reapplies the last undone step.
+    /// Nothing is redone if a step changing the data is committed after undoing, since it discards the steps to redo.
+    pub fn redo(&mut self, data: &mut [u8]) -> bool {
+        if self.commit(data) {
+            return false;
+        }
+        let Some(step) = self.redo.pop() else {
+            return false;
+        };
+        for change in step.iter() {
+            data[change.range()].copy_from_slice(&change.after);
+        }
+        self.push(step);
+        true
+    }
+
+    fn push(&mut self, step: Step) {
+        self.undo.push_back(step);
+        while self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Finds the runs of the scan lines which differ from the ones copied at the start of the step.
+    fn changes(&self, pending: &Pending, data: &[u8]) -> Vec<Change> {
+        let end = pending.start + pending.before.len();
+        if data.len() != self.size || end > data.len() {
+            return vec![];
+        }
+        let width = self.layout.scan_line_width.max(1);
+        let mut changes = vec![];
+        let mut run: Option<Range<usize>> = None;
+        let lines = pending.before.chunks(width).zip(data[pending.start..end].chunks(width));
+        for (index, (before, current)) in lines.enumerate() {
+            let start = pending.start + index * width;
+            let end = start + before.len();
+            if before != current {
+                run = Some(run.map_or(start..end, |run| run.start..end));
+            } else if let Some(run) = run.take() {
+                changes.push(Self::change(pending, data, run));
+            }
+        }
+        if let Some(run) = run {
+            changes.push(Self::change(pending, data, run));
+        }
+        changes
+    }
+
+    fn change(pending: &Pending, data: &[u8], range: Range<usize>) -> Change {
+        let offset = range.start - pending.start..range.end - pending.start;
+        Change {
+            start: range.start,
+            before: pending.before[offset].to_vec(),
+            after: data[range].to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn layout(scan_line_width: usize) -> Layout {
+        Layout {
+            scan_line_width,
+            width: scan_line_width as u32 - 1,
+            color_type: ColorType::GrayScale,
+            bit_depth: 8,
+        }
+    }
+
+    #[test]
+    fn test_commit_records_changed_lines() {
+        let mut data: Vec<u8> = (0..12).collect();
+        let mut history = History::new(&data, layout(3), 10);
+        assert!(!history.commit(&data));
+        history.begin(&data, 0, 4);
+        assert!(!history.commit(&data));
+
+        history.begin(&data, 0, 4);
+        data[1] = 100;
+        data[4] = 101;
+        data[10] = 102;
+        assert!(history.commit(&data));
+        assert_eq!(
+            vec![
+                Change {
+                    start: 0,
+                    before: vec![0, 1, 2, 3, 4, 5],
+                    after: vec![0, 100, 2, 3, 101, 5]
+                },
+                Change {
+                    start: 9,
+                    before: vec![9, 10, 11],
+                    after: vec![9, 102, 11]
+                },
+            ],
+            history.undo[0]
+        );
+    }
+
+    #[test]
+    fn test_begin_copies_only_the_lines() {
+        let mut data: Vec<u8> = (0..12).collect();
+        let mut history = History::new(&data, layout(3), 10);
+        history.begin(&data, 1, 2);
+        assert_eq!(6, history.pending.as_ref().map_or(0, |pending| pending.before.len()));
+
+        data[4] = 100;
+        data[10] = 101;
+        assert!(history.commit(&data));
+        assert_eq!(
+            vec![Change {
+                start: 3,
+                before: vec![3, 4, 5],
+                after: vec![3, 100, 5]
+            }],
+            history.undo[0]
+        );
+    }
+
+    #[test]
+    fn test_undo_and_redo() {
+        let original: Vec<u8> = (0..12).collect();
+        let mut data = original.clone();
+        let mut history = History::new(&data, layout(3), 10);
+        history.begin(&data, 0, 4);
+        data[7] = 100;
+        history.begin(&data, 0, 4);
+        data[0] = 101;
+        let edited = data.clone();
+
+        assert!(history.undo(&mut data));
+        assert_eq!(100, data[7]);
+        assert_eq!(0, data[0]);
+        assert!(history.undo(&mut data));
+        assert_eq!(original, data);
+        assert!(!history.undo(&mut data));
+
+        assert!(history.redo(&mut data));
+        assert!(history.redo(&mut data));
+        assert_eq!(edited, data);
+        assert!(!history.redo(&mut data));
+    }
+
+    #[test]
+    fn test_change_discards_redo() {
+        let mut data: Vec<u8> = (0..12).collect();
+        let mut history = History::new(&data, layout(3), 10);
+        history.begin(&data, 0, 4);
+        data[7] = 100;
+        history.undo(&mut data);
+        assert!(history.can_redo());
+
+        history.begin(&data, 0, 1);
+        data[0] = 101;
+        assert!(!history.redo(&mut data));
+        assert!(!history.can_redo());
+        assert_eq!(101, data[0]);
+    }
+
+    #[test]
+    fn test_limit() {
+        let original: Vec<u8> = (0..12).collect();
+        let mut data = original.clone();
+        let mut history = History::new(&data, layout(3), 2);
+        for index in 0..3 {
+            history.begin(&data, index, 1);
+            data[index * 3] = 100;
+            history.commit(&data);
+        }
+        assert!(history.undo(&mut data));
+        assert!(history.undo(&mut data));
+        assert!(!history.undo(&mut data));
+        assert_eq!(100, data[0]);
+        assert_eq!(&original[3..], &data[3..]);
+    }
+}
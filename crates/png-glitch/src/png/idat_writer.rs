@@ -0,0 +1,74 @@
+use crate::operation::Encode;
+use crate::png::parser::{Chunk, ChunkType};
+use std::io::Write;
+
+/// The maximum size of the data in an IDAT chunk emitted by IdatWriter.
+const MAX_CHUNK_DATA_SIZE: usize = 1 << 16;
+
+/// IdatWriter splits compressed data into IDAT chunks, and writes them to the inner writer.
+/// A chunk is emitted every time the buffered data reaches its maximum size.
+pub struct IdatWriter<W: Write> {
+    inner: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> IdatWriter<W> {
+    pub fn new(inner: W) -> IdatWriter<W> {
+        IdatWriter {
+            inner,
+            buffer: Vec::with_capacity(MAX_CHUNK_DATA_SIZE),
+        }
+    }
+
+    /// This method writes the buffered data as the last IDAT chunk, and returns the inner writer.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        if !self.buffer.is_empty() {
+            self.emit()?;
+        }
+        Ok(self.inner)
+    }
+
+    fn emit(&mut self) -> std::io::Result<()> {
+        let data = std::mem::replace(&mut self.buffer, Vec::with_capacity(MAX_CHUNK_DATA_SIZE));
+        let mut crc = crc32fast::Hasher::new();
+        crc.update(ChunkType::IDAT);
+        crc.update(&data);
+        let chunk = Chunk::new(ChunkType::Data, data, crc.finalize().to_be_bytes());
+        chunk.encode(&mut self.inner)
+    }
+}
+
+impl<W: Write> Write for IdatWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let size = buf.len().min(MAX_CHUNK_DATA_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..size]);
+        if self.buffer.len() == MAX_CHUNK_DATA_SIZE {
+            self.emit()?;
+        }
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_into_chunks() -> anyhow::Result<()> {
+        let mut writer = IdatWriter::new(vec![]);
+        writer.write_all(&vec![7; MAX_CHUNK_DATA_SIZE + 10])?;
+        let buffer = writer.finish()?;
+
+        let mut reader = buffer.as_slice();
+        let first = Chunk::read(&mut reader, 0, true)?.expect("The first IDAT chunk should be written");
+        let second = Chunk::read(&mut reader, 0, true)?.expect("The second IDAT chunk should be written");
+        assert_eq!(MAX_CHUNK_DATA_SIZE, first.length());
+        assert_eq!(10, second.length());
+        assert!(Chunk::read(&mut reader, 0, true)?.is_none());
+        Ok(())
+    }
+}
@@ -1,10 +1,12 @@
-use anyhow::Context;
+use std::io::{ErrorKind, Read};
+
 use fdeflate::Decompressor;
 
-use crate::png::png_error::PngError;
-use crate::png::{Png, SIGNATURE};
+use crate::png::png_error::{Error, PngError};
+use crate::png::{FilterType, Png, UnknownFilterPolicy, SIGNATURE};
+use std::sync::Arc;
 
-pub use crate::png::parser::chunk::{Chunk, ChunkType};
+pub use crate::png::parser::chunk::{Chunk, ChunkType, Source};
 pub use crate::png::parser::header::Header;
 pub use crate::png::parser::terminator::Terminator;
 pub use header::ColorType;
@@ -13,29 +15,96 @@ mod chunk;
 mod header;
 mod terminator;
 
+/// The default maximum size of decoded data, to avoid huge allocation caused by a broken IHDR chunk.
+/// Images larger than this can be parsed by raising `ParseOptions::max_decoded_size`.
+pub const MAX_DECODED_DATA_SIZE: usize = 1 << 30;
+
+/// ParseOptions controls how PNG images are parsed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ParseOptions {
+    /// The maximum size of the decoded scan lines in bytes, including their filter type bytes.
+    /// Images whose IHDR chunk requires more are rejected with `PngError::TooLargeImage`.
+    pub max_decoded_size: usize,
+    /// Chunks whose CRC does not match their content are rejected with `PngError::CrcMismatch` when this is true.
+    /// Turn it off to parse images edited with a hex editor or damaged by other tools. Such chunks are written with their original CRC.
+    pub verify_crc: bool,
+    /// How scan lines with an unknown filter type are handled. With `UnknownFilterPolicy::Error`,
+    /// such images are rejected with `PngError::InvalidFilterType` located at the first IDAT chunk.
+    pub unknown_filter_policy: UnknownFilterPolicy,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            max_decoded_size: MAX_DECODED_DATA_SIZE,
+            verify_crc: true,
+            unknown_filter_policy: UnknownFilterPolicy::default(),
+        }
+    }
+}
+
+/// The size of the buffer for decoded data grows by this size, so that memory is allocated only for the data in IDAT chunks.
+const DECODED_DATA_GROWTH: usize = 1 << 16;
+
 pub struct Parser {
+    options: ParseOptions,
+    copies_payload: bool,
     header: Option<Header>,
     terminator: Option<Terminator>,
+    decompressor: Decompressor,
     data: Vec<u8>,
+    decoded_size: usize,
+    has_idat: bool,
+    idat_offset: u64,
     misc: Vec<Chunk>,
+    offset: u64,
+    chunk_type: Option<[u8; 4]>,
 }
 
 impl Parser {
-    pub fn parse(buffer: &[u8]) -> anyhow::Result<Png> {
-        if buffer.starts_with(SIGNATURE) {
-            let mut parser = Self::new();
-            parser.parse_chunks(&buffer[8..])?;
+    /// This method parses the PNG image read from the reader chunk by chunk.
+    /// IDAT chunks are inflated as soon as they are read, so that their compressed data is not held in memory.
+    pub fn read(mut reader: impl Read, options: &ParseOptions) -> Result<Png, Error> {
+        let mut signature = [0; 8];
+        match reader.read_exact(&mut signature) {
+            Ok(_) if signature == SIGNATURE => {
+                let mut parser = Self::new(options);
+                parser.parse_chunks(reader)?;
+                parser.build()
+            }
+            Err(error) if error.kind() != ErrorKind::UnexpectedEof => Err(error.into()),
+            _ => Err(Error::parse(PngError::InvalidSignature, 0, None)),
+        }
+    }
+
+    /// This method parses the PNG image in the source without copying the payload of chunks.
+    /// Chunks other than IDAT borrow the source, and IDAT chunks are inflated directly from it.
+    #[cfg(any(feature = "mmap", test))]
+    pub fn parse_source(source: Source, options: &ParseOptions) -> Result<Png, Error> {
+        Self::parse_shared(source, options, false)
+    }
+
+    /// This method parses the PNG image in the buffer. IDAT chunks are inflated directly from the buffer,
+    /// while the payload of the other chunks is copied, so that the buffer is released after parsing.
+    pub fn parse_buffer(buffer: Vec<u8>, options: &ParseOptions) -> Result<Png, Error> {
+        Self::parse_shared(Arc::new(buffer), options, true)
+    }
+
+    fn parse_shared(source: Source, options: &ParseOptions, copies_payload: bool) -> Result<Png, Error> {
+        if (*source).as_ref().starts_with(SIGNATURE) {
+            let mut parser = Self::new(options);
+            parser.copies_payload = copies_payload;
+            parser.parse_borrowed_chunks(&source)?;
             parser.build()
         } else {
-            Err(PngError::InvalidSignature).context("Invalid signature found on parsing png file.")
+            Err(Error::parse(PngError::InvalidSignature, 0, None))
         }
     }
 
-    fn parse_chunks(&mut self, buffer: &[u8]) -> anyhow::Result<()> {
-        let mut index = 0;
-        while index < buffer.len() {
-            let chunk = Chunk::parse(&buffer[index..])?;
-            index += chunk.consumed_size();
+    fn parse_borrowed_chunks(&mut self, source: &Source) -> Result<(), Error> {
+        let length = (**source).as_ref().len();
+        while (self.offset as usize) < length {
+            let chunk = Chunk::parse(source, self.offset as usize, self.options.verify_crc)?;
             self.found_chunk(chunk)?;
             if self.has_iend() {
                 break;
@@ -44,23 +113,50 @@ impl Parser {
         Ok(())
     }
 
-    fn build(self) -> anyhow::Result<Png> {
-        let data = self.deflate()?;
-        let header = self.header.ok_or(PngError::NoIHDRFound)?;
-        let terminator = self.terminator.ok_or(PngError::NOIENDFound)?;
+    fn parse_chunks(&mut self, mut reader: impl Read) -> Result<(), Error> {
+        while let Some(chunk) = Chunk::read(&mut reader, self.offset, self.options.verify_crc)? {
+            self.found_chunk(chunk)?;
+            if self.has_iend() {
+                break;
+            }
+        }
+        Ok(())
+    }
 
-        Ok(Png::new(header, terminator, self.misc, data))
+    fn build(mut self) -> Result<Png, Error> {
+        self.chunk_type = None;
+        self.finish_inflation()?;
+        let header = self.header.ok_or(Error::parse(PngError::NoIHDRFound, self.offset, None))?;
+        let terminator = self.terminator.ok_or(Error::parse(PngError::NOIENDFound, self.offset, None))?;
+        if self.options.unknown_filter_policy == UnknownFilterPolicy::Error && has_unknown_filter_type(&self.data, header.scan_line_width()) {
+            return Err(Error::parse(PngError::InvalidFilterType, self.idat_offset, Some(*b"IDAT")));
+        }
+
+        Ok(Png::new(header, terminator, self.misc, self.data, &self.options))
     }
 
-    fn new() -> Parser {
+    fn new(options: &ParseOptions) -> Parser {
         Parser {
+            options: *options,
+            copies_payload: false,
             header: None,
             terminator: None,
+            decompressor: Decompressor::new(),
             data: vec![],
+            decoded_size: 0,
+            has_idat: false,
+            idat_offset: 0,
             misc: vec![],
+            offset: SIGNATURE.len() as u64,
+            chunk_type: None,
         }
     }
 
+    /// Creates an error located at the chunk being parsed.
+    fn error(&self, error: PngError) -> Error {
+        Error::parse(error, self.offset, self.chunk_type)
+    }
+
     fn has_ihdr(&self) -> bool {
         self.header.is_some()
     }
@@ -70,10 +166,15 @@ impl Parser {
     }
 
     fn has_idat(&self) -> bool {
-        !self.data.is_empty()
+        self.has_idat
     }
 
-    fn found_chunk(&mut self, chunk: Chunk) -> anyhow::Result<()> {
+    fn found_chunk(&mut self, mut chunk: Chunk) -> Result<(), Error> {
+        if self.copies_payload && chunk.chunk_type != ChunkType::Data {
+            chunk.data = chunk.data.into_owned();
+        }
+        self.chunk_type = chunk.chunk_type.to_bytes();
+        let consumed_size = chunk.consumed_size() as u64;
         match chunk.chunk_type {
             ChunkType::Start => self.found_ihdr(chunk),
             ChunkType::End => self.found_iend(chunk),
@@ -83,29 +184,37 @@ impl Parser {
                 Ok(())
             }
         }
+        .map_err(|error| self.error(error))?;
+        self.offset += consumed_size;
+        Ok(())
     }
 
-    fn found_ihdr(&mut self, chunk: Chunk) -> anyhow::Result<()> {
+    fn found_ihdr(&mut self, chunk: Chunk) -> Result<(), PngError> {
         if self.has_ihdr() {
-            Err(PngError::DuplicateIHDRFound).context("IHDR should appear only once.")
+            Err(PngError::DuplicateIHDRFound)
         } else {
-            self.header = Some(chunk.try_into()?);
+            let header: Header = chunk.try_into()?;
+            self.decoded_size = header
+                .scan_line_width()
+                .checked_mul(header.height() as usize)
+                .filter(|size| *size <= self.options.max_decoded_size)
+                .ok_or(PngError::TooLargeImage)?;
+            self.header = Some(header);
             Ok(())
         }
     }
 
-    fn found_idat(&mut self, mut chunk: Chunk) -> anyhow::Result<()> {
-        if chunk.chunk_type == ChunkType::Data {
-            self.data.append(&mut chunk.data);
-            Ok(())
-        } else {
-            Err(PngError::InvalidChunkType(chunk)).context("IDAT is expected")
+    fn found_idat(&mut self, chunk: Chunk) -> Result<(), PngError> {
+        if !self.has_idat {
+            self.idat_offset = self.offset;
         }
+        self.has_idat = true;
+        self.inflate(&chunk.data)
     }
 
-    fn found_iend(&mut self, chunk: Chunk) -> anyhow::Result<()> {
+    fn found_iend(&mut self, chunk: Chunk) -> Result<(), PngError> {
         if self.has_iend() {
-            Err(PngError::DuplicateIENDFound).context("IEND should appear only once.")
+            Err(PngError::DuplicateIENDFound)
         } else {
             self.terminator = Some(chunk.try_into()?);
             Ok(())
@@ -116,18 +225,294 @@ impl Parser {
         self.misc.push(chunk)
     }
 
-    fn deflate(&self) -> anyhow::Result<Vec<u8>> {
+    /// Inflates the data in an IDAT chunk. Data beyond the size specified by IHDR chunk is ignored.
+    fn inflate(&mut self, mut input: &[u8]) -> Result<(), PngError> {
+        if !self.has_ihdr() {
+            return Err(PngError::NoIHDRFound);
+        }
+        while !input.is_empty() && !self.decompressor.is_done() && self.data.len() < self.decoded_size {
+            let position = self.data.len();
+            self.data.resize((position + DECODED_DATA_GROWTH).min(self.decoded_size), 0);
+            let (consumed, produced) = self
+                .decompressor
+                .read(input, &mut self.data, position, false)
+                .map_err(|_| PngError::DeflateFailure)?;
+            self.data.truncate(position + produced);
+            input = &input[consumed..];
+        }
+        Ok(())
+    }
+
+    fn finish_inflation(&mut self) -> Result<(), Error> {
         if !self.has_idat() {
-            Err(PngError::NoIDATFound).context("Failed on parsing a PNG file.")
-        } else {
-            let header = self.header.as_ref().ok_or(PngError::NoIHDRFound)?;
-            let mut decompressor = Decompressor::new();
-            let mut buffer = vec![0; header.scan_line_width() * (header.height() as usize)];
-            let _ = decompressor
-                .read(&self.data, &mut buffer, 0, true)
-                .map_err(|_| PngError::DeflateFailure)
-                .context("Deflate failure while parsing consolidated IDAT chunks.")?;
-            Ok(buffer)
+            return Err(self.error(PngError::NoIDATFound));
+        }
+        if !self.decompressor.is_done() && self.data.len() < self.decoded_size {
+            return Err(self.error(PngError::DeflateFailure));
         }
+        self.data.resize(self.decoded_size, 0);
+        Ok(())
+    }
+}
+
+fn has_unknown_filter_type(data: &[u8], scan_line_width: usize) -> bool {
+    data.chunks(scan_line_width)
+        .any(|scan_line| matches!(FilterType::from(scan_line[0]), FilterType::Unknown(_)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::operation::{Encode, Filter};
+    use crate::png::parser::chunk::ChunkData;
+    use rand::rngs::StdRng;
+    use rand::{RngCore, SeedableRng};
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    /// The conformance corpus named after the PngSuite files. Refer to its README.md for how the files are made.
+    const PNG_SUITE: &str = "tests/pngsuite";
+
+    fn corpus(directory: &str) -> Vec<PathBuf> {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join(directory);
+        let mut files: Vec<PathBuf> = std::fs::read_dir(directory)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "png"))
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn file_name(path: &Path) -> String {
+        path.file_name().unwrap_or_default().to_string_lossy().to_string()
+    }
+
+    /// Files in PngSuite whose name starts with "x" are deliberately broken.
+    fn is_broken(name: &str) -> bool {
+        name.starts_with('x')
+    }
+
+    /// Decodes the image with the png crate without any transformation, and returns its unfiltered scan lines.
+    /// None is returned for interlaced images, which are not supported.
+    fn reference(bytes: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::IDENTITY);
+        decoder.ignore_checksums(true);
+        let mut reader = decoder.read_info()?;
+        if reader.info().interlaced {
+            return Ok(None);
+        }
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        buffer.truncate(info.buffer_size());
+        Ok(Some(buffer))
+    }
+
+    fn unfiltered(png: &mut Png) -> Vec<u8> {
+        png.remove_filter_from(0, png.height() as usize);
+        png.data
+            .borrow()
+            .chunks(png.scan_line_width())
+            .flat_map(|line| line[1..].to_vec())
+            .collect()
+    }
+
+    /// Encodes an image of the given color type and bit depth with the png crate, filling it with random samples.
+    fn generate(color_type: png::ColorType, bit_depth: png::BitDepth, width: u32, height: u32) -> anyhow::Result<Vec<u8>> {
+        let bits_per_pixel = color_type.samples() * bit_depth as usize;
+        let mut data = vec![0; (width as usize * bits_per_pixel).div_ceil(8) * height as usize];
+        StdRng::seed_from_u64(width as u64 * 31 + bits_per_pixel as u64).fill_bytes(&mut data);
+
+        let mut bytes = vec![];
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(bit_depth);
+        if color_type == png::ColorType::Indexed {
+            let mut palette = vec![0; 3 << (bit_depth as usize)];
+            StdRng::seed_from_u64(0).fill_bytes(&mut palette);
+            encoder.set_palette(palette);
+        }
+        encoder.write_header()?.write_image_data(&data)?;
+        Ok(bytes)
+    }
+
+    /// Checks that the image is parsed, decoded to the same pixels as the png crate, and survives encoding.
+    fn check_valid(name: &str, bytes: Vec<u8>) -> anyhow::Result<()> {
+        let options = ParseOptions::default();
+        let mut png = Parser::read(bytes.as_slice(), &options).map_err(|error| anyhow::anyhow!("{}: {}", name, error))?;
+        let borrowed = Parser::parse_source(Arc::new(bytes.clone()), &options)?;
+        assert_eq!(*png.data.borrow(), *borrowed.data.borrow(), "{} should be parsed without copy", name);
+        let Some(expected) = reference(&bytes)? else {
+            return Ok(());
+        };
+
+        let mut encoded = vec![];
+        png.encode(&mut encoded)?;
+        let mut another = Parser::read(encoded.as_slice(), &options)?;
+        assert_eq!(*png.data.borrow(), *another.data.borrow(), "{} should survive encoding", name);
+        assert_eq!(Some(&expected), reference(&encoded)?.as_ref(), "{} should be encoded properly", name);
+
+        assert_eq!(expected, unfiltered(&mut png), "{} should be decoded as the reference", name);
+        assert_eq!(expected, unfiltered(&mut another), "{} should be decoded as the reference", name);
+        Ok(())
+    }
+
+    /// Checks that the image is rejected with the error expected from its name, both by the reader and the borrowing parser.
+    fn check_broken(name: &str, bytes: Vec<u8>) {
+        let options = ParseOptions::default();
+        let error = match Parser::read(bytes.as_slice(), &options) {
+            Ok(_) => panic!("{} should not be parsed", name),
+            Err(error) => error,
+        };
+        let borrowed_error = match Parser::parse_source(Arc::new(bytes), &options) {
+            Ok(_) => panic!("{} should not be parsed", name),
+            Err(error) => error,
+        };
+        assert_eq!(format!("{:?}", error), format!("{:?}", borrowed_error), "{} should fail in the same way", name);
+        let error = match error {
+            Error::Parse { error, .. } => Some(error),
+            _ => None,
+        };
+        let Some(expected) = expected_error(name) else {
+            panic!("{} has no expected error. It should be listed in expected_error", name);
+        };
+        assert_eq!(Some(expected), error, "{} should fail with a specific error", name);
+    }
+
+    /// The error expected for each broken image in PngSuite.
+    fn expected_error(name: &str) -> Option<PngError> {
+        let error = match name {
+            "xs1n0g01.png" | "xs2n0g01.png" | "xs4n0g01.png" | "xs7n0g01.png" => PngError::InvalidSignature,
+            "xcrn0g04.png" | "xlfn0g04.png" => PngError::InvalidSignature,
+            "xhdn0g08.png" | "xcsn0g01.png" => PngError::CrcMismatch,
+            "xc1n0g08.png" | "xc9n2c08.png" => PngError::InvalidColorType,
+            "xd0n2c08.png" | "xd3n2c08.png" | "xd9n2c08.png" => PngError::InvalidBitDepth,
+            "xdtn0g01.png" => PngError::NoIDATFound,
+            _ => return None,
+        };
+        Some(error)
+    }
+
+    #[test]
+    fn test_valid_images() -> anyhow::Result<()> {
+        for path in corpus("etc") {
+            check_valid(&file_name(&path), std::fs::read(&path)?)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_png_suite() -> anyhow::Result<()> {
+        let files = corpus(PNG_SUITE);
+        assert!(!files.is_empty(), "No PNG file is found in {}", PNG_SUITE);
+        for path in files {
+            let name = file_name(&path);
+            let bytes = std::fs::read(&path)?;
+            if is_broken(&name) {
+                check_broken(&name, bytes);
+            } else {
+                check_valid(&name, bytes)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_buffer_copies_chunks() -> anyhow::Result<()> {
+        let bytes = include_bytes!("../../etc/sample00.png").to_vec();
+        let png = Parser::parse_buffer(bytes, &ParseOptions::default())?;
+        let chunks = png.misc_chunks.iter().chain([&png.header.inner, &png.terminator.inner]);
+        for chunk in chunks {
+            assert!(matches!(chunk.data, ChunkData::Owned(_)), "{:?} should not refer to the buffer", chunk.chunk_type);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_crc_verification() -> anyhow::Result<()> {
+        let valid = generate(png::ColorType::Rgb, png::BitDepth::Eight, 32, 32)?;
+        let mut broken = valid.clone();
+        let last = broken.len() - 1;
+        broken[last] ^= 1;
+        let ihdr_crc = SIGNATURE.len() + 8 + 13;
+        broken[ihdr_crc] ^= 1;
+
+        let options = ParseOptions { verify_crc: false, ..ParseOptions::default() };
+        let expected = Parser::read(valid.as_slice(), &ParseOptions::default())?;
+        let png = Parser::read(broken.as_slice(), &options)?;
+        let borrowed = Parser::parse_source(Arc::new(broken.clone()), &options)?;
+        assert_eq!(*expected.data.borrow(), *png.data.borrow());
+        assert_eq!(*expected.data.borrow(), *borrowed.data.borrow());
+        assert!(Parser::read(broken.as_slice(), &ParseOptions::default()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_location() {
+        let mut bytes = include_bytes!("../../etc/none.png").to_vec();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        match Parser::read(bytes.as_slice(), &ParseOptions::default()) {
+            Err(Error::Parse { error, offset, chunk_type }) => {
+                assert_eq!(PngError::CrcMismatch, error);
+                assert_eq!(bytes.len() as u64 - 12, offset);
+                assert_eq!(Some(*b"IEND"), chunk_type);
+            }
+            other => panic!("CRC mismatch of IEND should be reported: {:?}", other.err()),
+        }
+
+        let ihdr = SIGNATURE.len();
+        bytes[ihdr + 8 + 9] = 5;
+        let crc = crc32fast::hash(&bytes[ihdr + 4..ihdr + 8 + 13]).to_be_bytes();
+        bytes[ihdr + 8 + 13..ihdr + 8 + 17].copy_from_slice(&crc);
+        match Parser::parse_source(Arc::new(bytes), &ParseOptions::default()) {
+            Err(Error::Parse { error, offset, chunk_type }) => {
+                assert_eq!(PngError::InvalidColorType, error);
+                assert_eq!(ihdr as u64, offset);
+                assert_eq!(Some(*b"IHDR"), chunk_type);
+            }
+            other => panic!("Invalid color type should be reported: {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn test_max_decoded_size() -> anyhow::Result<()> {
+        let bytes = include_bytes!("../../etc/none.png");
+        let png = Parser::read(bytes.as_slice(), &ParseOptions::default())?;
+        let size = png.data.borrow().len();
+
+        let options = ParseOptions { max_decoded_size: size, ..ParseOptions::default() };
+        assert!(Parser::read(bytes.as_slice(), &options).is_ok());
+        let options = ParseOptions { max_decoded_size: size - 1, ..ParseOptions::default() };
+        match Parser::parse_source(Arc::new(bytes.to_vec()), &options) {
+            Err(Error::Parse { error, chunk_type, .. }) => {
+                assert_eq!(PngError::TooLargeImage, error);
+                assert_eq!(Some(*b"IHDR"), chunk_type);
+            }
+            other => panic!("The image should be rejected as too large: {:?}", other.err()),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_filter_policy_error() -> anyhow::Result<()> {
+        let png = Parser::read(include_bytes!("../../etc/none.png").as_slice(), &ParseOptions::default())?;
+        png.data.borrow_mut()[png.header.scan_line_width() * 3] = 9;
+        let mut bytes = vec![];
+        png.encode(&mut bytes)?;
+
+        assert!(Parser::read(bytes.as_slice(), &ParseOptions::default()).is_ok());
+        let options = ParseOptions { unknown_filter_policy: UnknownFilterPolicy::Error, ..ParseOptions::default() };
+        match Parser::read(bytes.as_slice(), &options) {
+            Err(Error::Parse { error, chunk_type, .. }) => {
+                assert_eq!(PngError::InvalidFilterType, error);
+                assert_eq!(Some(*b"IDAT"), chunk_type);
+            }
+            other => panic!("The image should be rejected for its filter type: {:?}", other.err()),
+        }
+        Ok(())
     }
 }
@@ -1,14 +1,16 @@
 use crate::operation::Encode;
+pub use crate::png::parser::chunk::chunk_data::{ChunkData, Source};
 pub use crate::png::parser::chunk::chunk_type::ChunkType;
-use crate::png::png_error::PngError;
-use anyhow::Context;
+use crate::png::png_error::{Error, PngError};
+use std::io::{ErrorKind, Read};
 
+mod chunk_data;
 mod chunk_type;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Chunk {
     pub chunk_type: ChunkType,
-    pub data: Vec<u8>,
+    pub data: ChunkData,
     pub crc: [u8; 4],
 }
 
@@ -21,53 +23,114 @@ impl Chunk {
         self.length() + 12
     }
 
-    pub fn new(chunk_type: ChunkType, data: Vec<u8>, crc: [u8; 4]) -> Chunk {
+    pub fn new(chunk_type: ChunkType, data: impl Into<ChunkData>, crc: [u8; 4]) -> Chunk {
         Chunk {
             chunk_type,
-            data,
+            data: data.into(),
             crc,
         }
     }
 
-    pub fn parse(buffer: &[u8]) -> anyhow::Result<Chunk> {
-        let length = Self::parse_length(buffer)?;
-        let chunk_type = Self::parse_chunk_type(&buffer[4..])?;
-        let data = Self::parse_data(&buffer[8..], length)?;
-        let crc = Self::parse_crc(&buffer[length + 8..])?;
+    /// This method creates a chunk with the CRC calculated from the chunk type and the data.
+    pub fn with_crc(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
+        let crc = Self::calculate_crc(&chunk_type, &data);
+        Chunk::new(chunk_type, data, crc)
+    }
+
+    /// This method parses the chunk starting at `offset` in the source. The payload of the chunk borrows the source.
+    /// The CRC is checked against the chunk type and the payload only when `verify_crc` is true.
+    pub fn parse(source: &Source, offset: usize, verify_crc: bool) -> Result<Chunk, Error> {
+        let error = |error, chunk_type: Option<&ChunkType>| Error::parse(error, offset as u64, chunk_type.and_then(ChunkType::to_bytes));
+        let buffer = (**source).as_ref().get(offset..).unwrap_or_default();
+        let length = buffer.first_chunk().ok_or(error(PngError::TooShortInput, None))?;
+        let length = Self::parse_length(length);
+        let chunk_type = Self::parse_chunk_type(&buffer[4..]).map_err(|e| error(e, None))?;
+        let end = 8usize
+            .checked_add(length)
+            .filter(|end| *end <= buffer.len())
+            .ok_or(error(PngError::TooShortInput, Some(&chunk_type)))?;
+        let crc = *buffer[end..].first_chunk().ok_or(error(PngError::TooShortInput, Some(&chunk_type)))?;
+        if verify_crc {
+            Self::verify_crc(&chunk_type, &buffer[8..end], crc).map_err(|e| error(e, Some(&chunk_type)))?;
+        }
 
+        let data = ChunkData::borrowed(source.clone(), offset + 8..offset + end);
         Ok(Chunk::new(chunk_type, data, crc))
     }
 
-    fn parse_length(buffer: &[u8]) -> anyhow::Result<usize> {
-        let array = buffer[..4].try_into().context("Failed to retrieve data size of a chunk")?;
-        let length = u32::from_be_bytes(array);
-        Ok(length as usize)
+    /// This method reads a chunk from the reader. None is returned when the reader reaches its end before the chunk.
+    /// `offset` is the position of the chunk in the whole data, which is reported on errors.
+    /// The CRC is checked against the chunk type and the payload only when `verify_crc` is true.
+    pub fn read(mut reader: impl Read, offset: u64, verify_crc: bool) -> Result<Option<Chunk>, Error> {
+        let error = |error, chunk_type: Option<&ChunkType>| Error::parse(error, offset, chunk_type.and_then(ChunkType::to_bytes));
+        let mut length = [0; 4];
+        match read_bytes(&mut reader, &mut length)? {
+            0 => return Ok(None),
+            4 => {}
+            _ => return Err(error(PngError::TooShortInput, None)),
+        }
+        let length = Self::parse_length(&length);
+        let mut buffer = [0; 4];
+        if read_bytes(&mut reader, &mut buffer)? < buffer.len() {
+            return Err(error(PngError::TooShortInput, None));
+        }
+        let chunk_type = Self::parse_chunk_type(&buffer).map_err(|e| error(e, None))?;
+        let mut data = vec![];
+        (&mut reader).take(length as u64).read_to_end(&mut data)?;
+        if data.len() < length || read_bytes(&mut reader, &mut buffer)? < buffer.len() {
+            return Err(error(PngError::TooShortInput, Some(&chunk_type)));
+        }
+        if verify_crc {
+            Self::verify_crc(&chunk_type, &data, buffer).map_err(|e| error(e, Some(&chunk_type)))?;
+        }
+
+        Ok(Some(Chunk::new(chunk_type, data, buffer)))
+    }
+
+    fn parse_length(buffer: &[u8; 4]) -> usize {
+        u32::from_be_bytes(*buffer) as usize
     }
 
-    fn parse_chunk_type(buffer: &[u8]) -> anyhow::Result<ChunkType> {
+    fn parse_chunk_type(buffer: &[u8]) -> Result<ChunkType, PngError> {
         ChunkType::new(buffer)
     }
 
-    fn parse_data(buffer: &[u8], length: usize) -> anyhow::Result<Vec<u8>> {
-        if buffer.len() < length {
-            Err(PngError::TooShortInput).context("Failed to parse payload of a chunk")
+    fn verify_crc(chunk_type: &ChunkType, data: &[u8], crc: [u8; 4]) -> Result<(), PngError> {
+        if Self::calculate_crc(chunk_type, data) == crc {
+            Ok(())
         } else {
-            Ok(buffer[..length].to_vec())
+            Err(PngError::CrcMismatch)
         }
     }
 
-    fn parse_crc(buffer: &[u8]) -> anyhow::Result<[u8; 4]> {
-        buffer[..4].try_into().context("Failed to retrieve CRC")
+    fn calculate_crc(chunk_type: &ChunkType, data: &[u8]) -> [u8; 4] {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(chunk_type.as_bytes());
+        hasher.update(data);
+        hasher.finalize().to_be_bytes()
+    }
+}
+
+/// Reads bytes until the buffer is filled or the reader reaches its end, and returns the number of read bytes.
+fn read_bytes(mut reader: impl Read, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(size) => filled += size,
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        }
     }
+    Ok(filled)
 }
 
 impl Encode for Chunk {
-    fn encode(&self, mut writer: impl std::io::Write) -> anyhow::Result<()> {
+    fn encode(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
         writer.write_all(&(self.length() as u32).to_be_bytes())?;
         self.chunk_type.encode(&mut writer)?;
         writer.write_all(&self.data)?;
         writer.write_all(&self.crc)?;
-        writer.flush()?;
-        Ok(())
+        writer.flush()
     }
 }
\ No newline at end of file
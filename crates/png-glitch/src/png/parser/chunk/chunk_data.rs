@@ -0,0 +1,71 @@
+use std::fmt::{Debug, Formatter};
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+
+/// Source is the whole input shared by the chunks parsed from it, such as a buffer or a memory mapped file.
+pub type Source = Arc<dyn AsRef<[u8]> + Send + Sync>;
+
+/// ChunkData is the payload of a chunk.
+/// It is either owned by the chunk, or borrowed from the input the chunk is parsed from.
+#[derive(Clone)]
+pub enum ChunkData {
+    Owned(Vec<u8>),
+    Borrowed { source: Source, range: Range<usize> },
+}
+
+impl ChunkData {
+    pub fn borrowed(source: Source, range: Range<usize>) -> ChunkData {
+        ChunkData::Borrowed { source, range }
+    }
+
+    /// This method copies the borrowed payload, so that the chunk no longer keeps the source alive.
+    pub fn into_owned(self) -> ChunkData {
+        match self {
+            ChunkData::Borrowed { .. } => ChunkData::Owned(self.to_vec()),
+            owned => owned,
+        }
+    }
+}
+
+impl Deref for ChunkData {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            ChunkData::Owned(data) => data,
+            ChunkData::Borrowed { source, range } => &(**source).as_ref()[range.clone()],
+        }
+    }
+}
+
+impl From<Vec<u8>> for ChunkData {
+    fn from(value: Vec<u8>) -> Self {
+        ChunkData::Owned(value)
+    }
+}
+
+impl Debug for ChunkData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkData::Owned(data) => write!(f, "owned {} bytes", data.len()),
+            ChunkData::Borrowed { range, .. } => write!(f, "borrowed {} bytes", range.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_borrowed() {
+        let source: Source = Arc::new(vec![0, 1, 2, 3, 4]);
+        let data = ChunkData::borrowed(source.clone(), 1..3);
+        assert_eq!(&[1, 2], &*data);
+        assert_eq!(2, Arc::strong_count(&source));
+
+        let data = data.into_owned();
+        assert_eq!(&[1, 2], &*data);
+        assert_eq!(1, Arc::strong_count(&source));
+    }
+}
@@ -1,9 +1,8 @@
 use std::fmt::{Debug, Formatter};
-use anyhow::Context;
 use crate::operation::Encode;
 use crate::png::png_error::PngError;
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum ChunkType {
     Start,
     Data,
@@ -12,12 +11,9 @@ pub enum ChunkType {
 }
 
 impl ChunkType {
-    pub fn new(bytes: &[u8]) -> anyhow::Result<ChunkType> {
+    pub fn new(bytes: &[u8]) -> Result<ChunkType, PngError> {
         if bytes.len() < 4 {
-            Err(PngError::TooShortInput).context(format!(
-                "Input has only {} bytes, while 4 bytes input is expected",
-                bytes.len()
-            ))
+            Err(PngError::TooShortInput)
         } else {
             let bytes = &bytes[0..4];
             let t = match bytes {
@@ -30,9 +26,31 @@ impl ChunkType {
         }
     }
 
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Start => ChunkType::IHDR,
+            Self::Data => ChunkType::IDAT,
+            Self::End => ChunkType::IEND,
+            Self::Other(bytes) => bytes,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Option<[u8; 4]> {
+        self.as_bytes().try_into().ok()
+    }
+
     pub const IHDR: &'static [u8] = &[73, 72, 68, 82];
     pub const IDAT: &'static [u8] = &[73, 68, 65, 84];
     pub const IEND: &'static [u8] = &[73, 69, 78, 68];
+    pub const PLTE: [u8; 4] = *b"PLTE";
+    pub const TRNS: [u8; 4] = *b"tRNS";
+
+    /// The chunks describing the colors in the image, which are invalidated on changing the color type or the bit depth.
+    const COLOR_DEPENDENT: [[u8; 4]; 5] = [Self::PLTE, Self::TRNS, *b"bKGD", *b"sBIT", *b"hIST"];
+
+    pub fn depends_on_color_type(&self) -> bool {
+        matches!(self, Self::Other(bytes) if Self::COLOR_DEPENDENT.contains(bytes))
+    }
 }
 
 impl Debug for ChunkType {
@@ -50,13 +68,7 @@ impl Debug for ChunkType {
 }
 
 impl Encode for ChunkType {
-    fn encode(&self, mut writer: impl std::io::Write) -> anyhow::Result<()> {
-        match self {
-            Self::Start => writer.write_all(ChunkType::IHDR),
-            Self::End => writer.write_all(ChunkType::IEND),
-            Self::Data => writer.write_all(ChunkType::IDAT),
-            Self::Other(t) => writer.write_all(t),
-        }?;
-        Ok(())
+    fn encode(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+        writer.write_all(self.as_bytes())
     }
 }
\ No newline at end of file
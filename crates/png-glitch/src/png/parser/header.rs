@@ -1,14 +1,15 @@
 use std::fmt::{Debug, Formatter};
-use anyhow::Context;
 
 use crate::operation::Encode;
 use crate::png::parser::chunk::{Chunk, ChunkType};
+use crate::png::png_error::PngError;
 pub use color_type::ColorType;
 use meta_data::MetaData;
 
 mod color_type;
 mod meta_data;
 
+#[derive(Clone)]
 pub struct Header {
     pub(crate) inner: Chunk, // for test
     metadata: MetaData,
@@ -18,10 +19,19 @@ pub struct Header {
 impl Header {
     fn new(width: u32, height: u32, bit_depth: u8, color_type: ColorType, inner: Chunk) -> Header {
         let metadata = MetaData::new(width, height, color_type, bit_depth);
-        let scanline_width = metadata.bits_per_scanline() / 8 + 1;
+        let scanline_width = metadata.bits_per_scanline().div_ceil(8) + 1;
         Header { inner, metadata, scanline_width }
     }
 
+    /// This method creates a header of a non-interlaced image.
+    pub fn create(width: u32, height: u32, color_type: ColorType, bit_depth: u8) -> Result<Header, PngError> {
+        let mut data = vec![];
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&[bit_depth, color_type.into(), 0, 0, 0]);
+        Header::try_from(Chunk::with_crc(ChunkType::Start, data))
+    }
+
     pub fn width(&self) -> u32 {
         self.metadata.width
     }
@@ -54,29 +64,37 @@ impl Header {
         chunk.data[8]
     }
 
-    fn parse_color_type(chunk: &Chunk) -> anyhow::Result<ColorType> {
-        ColorType::try_from(chunk.data[9]).context("Failed to retrieve color type.")
+    fn parse_color_type(chunk: &Chunk) -> Result<ColorType, PngError> {
+        ColorType::try_from(chunk.data[9])
     }
 }
 
 impl TryFrom<Chunk> for Header {
-    type Error = anyhow::Error;
+    type Error = PngError;
 
     fn try_from(chunk: Chunk) -> Result<Self, Self::Error> {
-        anyhow::ensure!(chunk.chunk_type == ChunkType::Start);
-        let header = Header::new(
-            Header::parse_width(&chunk),
-            Header::parse_height(&chunk),
-            Header::parse_bit_depth(&chunk),
-            Header::parse_color_type(&chunk)?,
-            chunk,
-        );
-        Ok(header)
+        if chunk.chunk_type != ChunkType::Start {
+            return Err(PngError::InvalidChunkType);
+        }
+        if chunk.length() != 13 {
+            return Err(PngError::InvalidHeader);
+        }
+        let width = Header::parse_width(&chunk);
+        let height = Header::parse_height(&chunk);
+        if width == 0 || height == 0 {
+            return Err(PngError::EmptyImage);
+        }
+        let bit_depth = Header::parse_bit_depth(&chunk);
+        let color_type = Header::parse_color_type(&chunk)?;
+        if !color_type.bit_depths().contains(&bit_depth) {
+            return Err(PngError::InvalidBitDepth);
+        }
+        Ok(Header::new(width, height, bit_depth, color_type, chunk))
     }
 }
 
 impl Encode for Header {
-    fn encode(&self, writer: impl std::io::Write) -> anyhow::Result<()> {
+    fn encode(&self, writer: impl std::io::Write) -> std::io::Result<()> {
         self.inner.encode(writer)
     }
 }
@@ -1,6 +1,6 @@
 use crate::png::png_error::PngError;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum ColorType {
     GrayScale,
     TrueColor,
@@ -19,6 +19,37 @@ impl ColorType {
             Self::TrueColorAlpha => (bit_depth * 4) as usize,
         }
     }
+
+    /// This method returns the bit depths allowed for the color type.
+    pub fn bit_depths(&self) -> &'static [u8] {
+        match self {
+            Self::GrayScale => &[1, 2, 4, 8, 16],
+            Self::IndexColor => &[1, 2, 4, 8],
+            Self::TrueColor | Self::GrayScaleAlpha | Self::TrueColorAlpha => &[8, 16],
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        match self {
+            Self::GrayScale => 1,
+            Self::TrueColor => 3,
+            Self::IndexColor => 1,
+            Self::GrayScaleAlpha => 2,
+            Self::TrueColorAlpha => 4,
+        }
+    }
+}
+
+impl From<ColorType> for u8 {
+    fn from(value: ColorType) -> Self {
+        match value {
+            ColorType::GrayScale => 0,
+            ColorType::TrueColor => 2,
+            ColorType::IndexColor => 3,
+            ColorType::GrayScaleAlpha => 4,
+            ColorType::TrueColorAlpha => 6,
+        }
+    }
 }
 
 impl TryFrom<u8> for ColorType {
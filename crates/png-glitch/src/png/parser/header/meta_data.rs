@@ -1,6 +1,6 @@
 use crate::png::parser::header::color_type::ColorType;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct MetaData {
     pub width: u32,
     pub height: u32,
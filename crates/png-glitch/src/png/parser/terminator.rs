@@ -1,22 +1,34 @@
 use crate::operation::Encode;
 use crate::png::parser::chunk::{Chunk, ChunkType};
+use crate::png::png_error::PngError;
 
+#[derive(Clone)]
 pub struct Terminator {
     pub inner: Chunk,
 }
 
+impl Default for Terminator {
+    fn default() -> Self {
+        Terminator {
+            inner: Chunk::with_crc(ChunkType::End, vec![]),
+        }
+    }
+}
+
 impl TryFrom<Chunk> for Terminator {
-    type Error = anyhow::Error;
+    type Error = PngError;
 
     fn try_from(value: Chunk) -> Result<Self, Self::Error> {
-        anyhow::ensure!(value.chunk_type == ChunkType::End);
-        Ok(Terminator { inner: value })
+        if value.chunk_type == ChunkType::End {
+            Ok(Terminator { inner: value })
+        } else {
+            Err(PngError::InvalidChunkType)
+        }
     }
 }
 
 impl Encode for Terminator {
-    fn encode(&self, writer: impl std::io::Write) -> anyhow::Result<()> {
-
+    fn encode(&self, writer: impl std::io::Write) -> std::io::Result<()> {
         self.inner.encode(writer)
     }
 }
\ No newline at end of file
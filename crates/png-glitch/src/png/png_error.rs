@@ -1,7 +1,8 @@
-use crate::png::Chunk;
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+/// PngError describes why the given data is not accepted as a PNG image.
+#[non_exhaustive]
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PngError {
     #[error("Invalid signature found.")]
     InvalidSignature,
@@ -18,11 +19,95 @@ pub enum PngError {
     #[error("Another IEND chunk found.")]
     DuplicateIENDFound,
     #[error("Invalid chunk type.")]
-    InvalidChunkType(Chunk),
+    InvalidChunkType,
     #[error("Invalid color type.")]
     InvalidColorType,
+    #[error("Invalid IHDR chunk.")]
+    InvalidHeader,
     #[error("Invalid filter type.")]
     InvalidFilterType,
+    #[error("Invalid bit depth for the color type.")]
+    InvalidBitDepth,
+    #[error("The image has no pixel.")]
+    EmptyImage,
+    #[error("The image is too large to decode.")]
+    TooLargeImage,
+    #[error("CRC of a chunk does not match its content.")]
+    CrcMismatch,
     #[error("Failed to deflate data.")]
     DeflateFailure,
 }
+
+/// OperationError describes why an operation can not be applied to an image.
+#[non_exhaustive]
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationError {
+    #[error("The region is outside the image.")]
+    RegionOutOfBounds,
+    #[error("The resulting image has no pixel.")]
+    EmptyImage,
+    #[error("The resulting image is too large to create.")]
+    TooLargeImage,
+    #[error("Invalid bit depth for the color type.")]
+    InvalidBitDepth,
+    #[error("The size, color type, or bit depth in the layout does not match the image.")]
+    LayoutMismatch,
+    #[error("The number of filter types in the layout does not match the height of the image.")]
+    FilterCountMismatch,
+}
+
+/// SidecarError describes why the sidecar file of a raw file is not accepted as its layout.
+/// `line` is the line number in the sidecar file, counted from 1.
+#[non_exhaustive]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SidecarError {
+    #[error("Line {line} is not a key-value pair.")]
+    InvalidLine { line: usize },
+    #[error("Invalid value of {key} at line {line}.")]
+    InvalidValue { line: usize, key: &'static str },
+    #[error("Unknown key {key} at line {line}.")]
+    UnknownKey { line: usize, key: String },
+    #[error("{key} is missing.")]
+    MissingKey { key: &'static str },
+}
+
+/// Error is returned when a PNG image can not be parsed, read, written, or glitched.
+#[non_exhaustive]
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The data is not a valid PNG image.
+    /// `offset` is the byte offset of the chunk where the error is found, counted from the beginning of the data.
+    /// `chunk_type` is the type of the chunk, if the error is found in a chunk.
+    #[error("{error} (offset = {offset}{})", describe_chunk_type(.chunk_type))]
+    Parse {
+        error: PngError,
+        offset: u64,
+        chunk_type: Option<[u8; 4]>,
+    },
+    /// Reading or writing the data failed.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// An operation can not be applied to the image. No data is parsed, so there is no offset to report.
+    #[error(transparent)]
+    Operation(#[from] OperationError),
+    /// The sidecar file of a raw file does not describe its layout.
+    #[error(transparent)]
+    Sidecar(#[from] SidecarError),
+}
+
+impl Error {
+    pub(crate) fn parse(error: PngError, offset: u64, chunk_type: Option<[u8; 4]>) -> Error {
+        Error::Parse {
+            error,
+            offset,
+            chunk_type,
+        }
+    }
+}
+
+fn describe_chunk_type(chunk_type: &Option<[u8; 4]>) -> String {
+    match chunk_type {
+        Some(chunk_type) => format!(", chunk type = {}", String::from_utf8_lossy(chunk_type)),
+        None => String::new(),
+    }
+}
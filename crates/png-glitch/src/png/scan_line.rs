@@ -3,12 +3,15 @@ use std::io::{Read, Write};
 use std::ops::{Index, IndexMut, Range};
 use thiserror::Error;
 use crate::png::{ColorType, SharedDecodedData};
-pub use filter_type::FilterType;
+pub use filter_type::{FilterType, UnknownFilterPolicy};
 pub use memory_range::MemoryRange;
+pub use pixel::Pixel;
+pub(crate) use pixel::max_sample;
 
 mod filter_type;
 mod memory_range;
 mod filter;
+mod pixel;
 
 pub type UsizeRange = Range<usize>;
 
@@ -19,16 +22,18 @@ pub struct ScanLine {
     decoded_data: SharedDecodedData,
     color_type: ColorType,
     bit_depth: u8,
+    width: u32,
 }
 
 impl ScanLine {
-    fn new(filter_type: FilterType, decoded_data: SharedDecodedData, range: UsizeRange, color_type: ColorType, bit_depth: u8) -> ScanLine {
+    fn new(filter_type: FilterType, decoded_data: SharedDecodedData, range: UsizeRange, color_type: ColorType, bit_depth: u8, width: u32) -> ScanLine {
         ScanLine {
             filter_type,
             decoded_data,
             range,
             color_type,
             bit_depth,
+            width,
         }
     }
 
@@ -47,7 +52,7 @@ impl ScanLine {
             ColorType::GrayScaleAlpha => std::cmp::max(bits * 2 / 8, 1) as usize,
             ColorType::TrueColor => std::cmp::max(bits * 3 / 8, 1) as usize,
             ColorType::TrueColorAlpha => std::cmp::max(bits * 4 / 8, 1) as usize,
-            ColorType::IndexColor => (bits / 8) as usize,
+            ColorType::IndexColor => std::cmp::max(bits / 8, 1) as usize,
         }
     }
 
@@ -59,8 +64,16 @@ impl ScanLine {
 
     /// Remove filter applied to the scanline
     pub fn remove_filter(&mut self, other: Option<&ScanLine>) {
-        filter::remove(self, other);
-        self.set_filter_type(FilterType::None);
+        self.remove_filter_with(other, UnknownFilterPolicy::default());
+    }
+
+    /// Remove filter applied to the scanline. Unknown filter type is resolved with the given policy.
+    pub fn remove_filter_with(&mut self, other: Option<&ScanLine>, policy: UnknownFilterPolicy) {
+        if let Some(filter_type) = self.filter_type.resolve(policy) {
+            self.filter_type = filter_type;
+            filter::remove(self, other);
+            self.set_filter_type(FilterType::None);
+        }
     }
 
     /// This method returns the filter method applied to the scan line.
@@ -89,6 +102,34 @@ impl ScanLine {
         self.bit_depth
     }
 
+    /// This method returns the number of pixels in the scan line.
+    pub fn width(&self) -> usize {
+        self.width as usize
+    }
+
+    /// pixel method returns the pixel specified with the index parameter.
+    /// Samples are extracted according to the color type and the bit depth of the scan line.
+    pub fn pixel(&self, index: usize) -> Option<Pixel> {
+        pixel::read(self, index)
+    }
+
+    /// set_pixel method updates the pixel specified by the index with the given one.
+    pub fn set_pixel(&self, index: usize, pixel: &Pixel) {
+        pixel::write(self, index, pixel)
+    }
+
+    /// pixels method returns all the pixels in the scan line.
+    pub fn pixels(&self) -> Vec<Pixel> {
+        (0..self.width()).flat_map(|index| self.pixel(index)).collect()
+    }
+
+    /// set_pixels method updates the pixels in the scan line with the given ones from the beginning of the line.
+    pub fn set_pixels(&self, pixels: &[Pixel]) {
+        for (index, pixel) in pixels.iter().enumerate() {
+            self.set_pixel(index, pixel);
+        }
+    }
+
     /// index method returns a byte in a pixel_data specified with the index parameter
     pub fn index(&self, index: usize) -> Option<u8> {
         let pixel_data_range = self.pixel_data_range();
@@ -162,8 +203,8 @@ impl TryFrom<MemoryRange> for ScanLine {
             .first_byte()
             .ok_or(ScanLineError::InvalidMemoryRange)?;
 
-        let filter_type = FilterType::try_from(byte)?;
-         Ok(ScanLine::new(filter_type, value.decoded_data, value.range, value.color_type, value.bit_depth))
+        let filter_type = FilterType::from(byte);
+        Ok(ScanLine::new(filter_type, value.decoded_data, value.range, value.color_type, value.bit_depth, value.width))
     }
 }
 
@@ -183,7 +224,7 @@ mod test {
         buffer: SharedDecodedData,
     }
 
-    impl<'a> TestTarget {
+    impl TestTarget {
         fn new() -> Self {
             let buffer = vec![0, 1, 2, 3, 4, 5];
             let buffer = share_decoded_data(buffer);
@@ -195,7 +236,7 @@ mod test {
         }
 
         fn scan_line(&self) -> ScanLine {
-            ScanLine::new(FilterType::None, self.buffer.clone(), self.usize_range(), ColorType::TrueColorAlpha, 8)
+            ScanLine::new(FilterType::None, self.buffer.clone(), self.usize_range(), ColorType::TrueColorAlpha, 8, 1)
         }
     }
 
@@ -234,7 +275,7 @@ mod test {
             let mut buffer = vec![0; scan_line.size()];
 
             let result = scan_line.read(&mut buffer);
-            assert_eq!(true, result.is_ok());
+            assert!(result.is_ok());
             assert_eq!(scan_line.size(), buffer.len());
             assert_eq!(&scan_line.decoded_data.borrow()[1..], &buffer);
         }
@@ -248,7 +289,7 @@ mod test {
 
             let size = scan_line.size();
             let result = scan_line.read_to_end(&mut buffer);
-            assert_eq!(true, result.is_ok());
+            assert!(result.is_ok());
             assert_eq!(&scan_line.decoded_data.borrow()[1..], &buffer[0..size]);
         }
     }
@@ -264,7 +305,7 @@ mod test {
 
             let buffer = vec![10; size];
             let result = scan_line.write(&buffer);
-            assert_eq!(true, result.is_ok());
+            assert!(result.is_ok());
             assert_eq!(buffer.len(), result.unwrap());
             assert_eq!(&buffer, &scan_line.decoded_data.borrow()[1..]);
         }
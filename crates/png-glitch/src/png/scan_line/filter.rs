@@ -8,7 +8,7 @@ mod average;
 
 pub fn remove(line: &ScanLine, previous: Option<&ScanLine>) {
     match line.filter_type {
-        FilterType::None => {},
+        FilterType::None | FilterType::Unknown(_) => {},
         FilterType::Sub => sub::remove(line),
         FilterType::Up => up::remove(line, previous),
         FilterType::Average => average::remove(line, previous),
@@ -51,7 +51,7 @@ mod test {
     fn test_unit() {
         let original = vec![1, 0, 1, 2, 255, 1, 1, 1, 255];
         let target = Rc::new(RefCell::new(original.clone()));
-        let scanline = ScanLine::new(FilterType::Sub, target, 0..original.len(), ColorType::TrueColorAlpha, 8);
+        let scanline = ScanLine::new(FilterType::Sub, target, 0..original.len(), ColorType::TrueColorAlpha, 8, 2);
         apply(&scanline);
         remove(&scanline);
         for (before, after) in original.iter().zip(scanline.decoded_data.borrow().iter()) {
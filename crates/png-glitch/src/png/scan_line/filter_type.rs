@@ -1,5 +1,7 @@
-use crate::png::png_error::PngError;
-
+/// FilterType represents the filter method applied to a scan line.
+///
+/// Filter type bytes other than the ones defined in the specification are kept as `Unknown`,
+/// so that the scan lines glitched by writing arbitrary values into their filter type byte can be handled.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum FilterType {
     None,
@@ -7,19 +9,49 @@ pub enum FilterType {
     Up,
     Average,
     Paeth,
+    Unknown(u8),
 }
 
-impl TryFrom<u8> for FilterType {
-    type Error = PngError;
+impl FilterType {
+    /// This method resolves the filter type into a known one according to the given policy.
+    /// `None` is returned when the policy requires the scan line to be left untouched.
+    pub fn resolve(self, policy: UnknownFilterPolicy) -> Option<FilterType> {
+        match (self, policy) {
+            (FilterType::Unknown(_), UnknownFilterPolicy::AsNone) => Some(FilterType::None),
+            (FilterType::Unknown(value), UnknownFilterPolicy::Modulo) => Some(FilterType::from(value % 5)),
+            (FilterType::Unknown(_), UnknownFilterPolicy::Keep | UnknownFilterPolicy::Error) => None,
+            (filter_type, _) => Some(filter_type),
+        }
+    }
+}
 
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+/// UnknownFilterPolicy specifies how scan lines with an unknown filter type are handled on removing filters.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum UnknownFilterPolicy {
+    /// The scan line is treated as if no filter is applied to it.
+    #[default]
+    AsNone,
+    /// The filter type is taken from the value of the filter type byte modulo 5.
+    Modulo,
+    /// The scan line is left untouched, including its filter type byte.
+    Keep,
+    /// Parsing fails with `PngError::InvalidFilterType` when the image has a scan line with an unknown filter type.
+    /// Scan lines whose filter type byte is changed after parsing are left untouched as `Keep` does.
+    Error,
+}
+
+/// Any byte is converted into a filter type. Bytes other than 0 to 4 become `FilterType::Unknown`.
+///
+/// This replaces the former `TryFrom<u8>` implementation, which rejected those bytes with `PngError::InvalidFilterType`.
+impl From<u8> for FilterType {
+    fn from(value: u8) -> Self {
         match value {
-            0 => Ok(FilterType::None),
-            1 => Ok(FilterType::Sub),
-            2 => Ok(FilterType::Up),
-            3 => Ok(FilterType::Average),
-            4 => Ok(FilterType::Paeth),
-            _ => Err(PngError::InvalidFilterType),
+            0 => FilterType::None,
+            1 => FilterType::Sub,
+            2 => FilterType::Up,
+            3 => FilterType::Average,
+            4 => FilterType::Paeth,
+            _ => FilterType::Unknown(value),
         }
     }
 }
@@ -32,6 +64,31 @@ impl From<FilterType> for u8 {
             FilterType::Up => 2,
             FilterType::Average => 3,
             FilterType::Paeth => 4,
+            FilterType::Unknown(value) => value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unknown_filter_type() {
+        for value in 0..=u8::MAX {
+            let filter_type = FilterType::from(value);
+            assert_eq!(value, u8::from(filter_type));
         }
+        assert_eq!(FilterType::Unknown(5), FilterType::from(5));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_resolve() {
+        let unknown = FilterType::Unknown(7);
+        assert_eq!(Some(FilterType::None), unknown.resolve(UnknownFilterPolicy::AsNone));
+        assert_eq!(Some(FilterType::Up), unknown.resolve(UnknownFilterPolicy::Modulo));
+        assert_eq!(None, unknown.resolve(UnknownFilterPolicy::Keep));
+        assert_eq!(None, unknown.resolve(UnknownFilterPolicy::Error));
+        assert_eq!(Some(FilterType::Sub), FilterType::Sub.resolve(UnknownFilterPolicy::Keep));
+    }
+}
@@ -6,15 +6,17 @@ pub struct MemoryRange {
     pub(super) range: UsizeRange,
     pub(super) color_type: ColorType,
     pub(super) bit_depth: u8,
+    pub(super) width: u32,
 }
 
 impl MemoryRange {
-    pub fn new(decoded_data: SharedDecodedData, range: UsizeRange, color_type: ColorType, bit_depth: u8) -> MemoryRange {
+    pub fn new(decoded_data: SharedDecodedData, range: UsizeRange, color_type: ColorType, bit_depth: u8, width: u32) -> MemoryRange {
         MemoryRange {
             decoded_data,
             range,
             color_type,
-            bit_depth
+            bit_depth,
+            width,
         }
     }
 
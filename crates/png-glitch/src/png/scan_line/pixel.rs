@@ -0,0 +1,185 @@
+use crate::png::ColorType;
+use crate::ScanLine;
+
+const MAX_CHANNELS: usize = 4;
+
+/// Pixel holds the samples of a pixel in a scan line, such as red, green, blue and alpha.
+/// Each sample keeps its raw value, which is in the range of `0..2^bit_depth`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Pixel {
+    samples: [u16; MAX_CHANNELS],
+    channels: usize,
+}
+
+impl Pixel {
+    /// This method creates a pixel from the given samples. Samples more than 4 are ignored.
+    pub fn new(samples: &[u16]) -> Pixel {
+        let channels = samples.len().min(MAX_CHANNELS);
+        let mut pixel = Pixel {
+            samples: [0; MAX_CHANNELS],
+            channels,
+        };
+        pixel.samples[..channels].copy_from_slice(&samples[..channels]);
+        pixel
+    }
+
+    /// This method returns the samples in the pixel.
+    pub fn samples(&self) -> &[u16] {
+        &self.samples[..self.channels]
+    }
+
+    /// This method returns the sample of the specified channel.
+    pub fn sample(&self, channel: usize) -> Option<u16> {
+        self.samples().get(channel).copied()
+    }
+
+    /// This method updates the sample of the specified channel.
+    pub fn set_sample(&mut self, channel: usize, value: u16) {
+        if channel < self.channels {
+            self.samples[channel] = value;
+        }
+    }
+
+    /// This method returns the number of channels in the pixel.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// This method converts the pixel into RGB values, each of them is in the range of `0.0..=1.0`.
+    /// The palette is used to look up the color of an index color pixel.
+    pub(crate) fn to_rgb(self, color_type: ColorType, bit_depth: u8, palette: &[[u8; 3]]) -> [f32; 3] {
+        let max = max_sample(bit_depth) as f32;
+        let normalize = |value: u16| value as f32 / max;
+        match color_type {
+            ColorType::GrayScale | ColorType::GrayScaleAlpha => {
+                let value = normalize(self.samples[0]);
+                [value, value, value]
+            }
+            ColorType::TrueColor | ColorType::TrueColorAlpha => [
+                normalize(self.samples[0]),
+                normalize(self.samples[1]),
+                normalize(self.samples[2]),
+            ],
+            ColorType::IndexColor => match palette.get(self.samples[0] as usize) {
+                Some([r, g, b]) => [*r as f32 / 255.0, *g as f32 / 255.0, *b as f32 / 255.0],
+                None => {
+                    let value = normalize(self.samples[0]);
+                    [value, value, value]
+                }
+            },
+        }
+    }
+
+    /// This method returns the opacity of the pixel in the range of `0.0..=1.0`.
+    /// `transparency` is the alpha of each palette entry in tRNS chunk. Entries beyond it are opaque.
+    pub(crate) fn alpha(self, color_type: ColorType, bit_depth: u8, transparency: &[u8]) -> f32 {
+        let max = max_sample(bit_depth) as f32;
+        match color_type {
+            ColorType::GrayScaleAlpha => self.samples[1] as f32 / max,
+            ColorType::TrueColorAlpha => self.samples[3] as f32 / max,
+            ColorType::IndexColor => transparency.get(self.samples[0] as usize).map_or(1.0, |alpha| *alpha as f32 / 255.0),
+            _ => 1.0,
+        }
+    }
+}
+
+pub fn max_sample(bit_depth: u8) -> u16 {
+    ((1u32 << bit_depth.min(16)) - 1) as u16
+}
+
+pub fn read(line: &ScanLine, index: usize) -> Option<Pixel> {
+    if index >= line.width() {
+        return None;
+    }
+    let channels = line.color_type().channels();
+    let mut samples = [0; MAX_CHANNELS];
+    for (channel, sample) in samples.iter_mut().enumerate().take(channels) {
+        *sample = read_sample(line, index * channels + channel)?;
+    }
+    Some(Pixel::new(&samples[..channels]))
+}
+
+pub fn write(line: &ScanLine, index: usize, pixel: &Pixel) {
+    if index >= line.width() {
+        return;
+    }
+    let channels = line.color_type().channels();
+    for (channel, sample) in pixel.samples().iter().enumerate().take(channels) {
+        write_sample(line, index * channels + channel, *sample);
+    }
+}
+
+fn read_sample(line: &ScanLine, sample_index: usize) -> Option<u16> {
+    let bit_depth = line.bit_depth() as usize;
+    match bit_depth {
+        16 => {
+            let high = line.index(sample_index * 2)? as u16;
+            let low = line.index(sample_index * 2 + 1)? as u16;
+            Some(high << 8 | low)
+        }
+        8 => line.index(sample_index).map(|value| value as u16),
+        _ => {
+            let bit_offset = sample_index * bit_depth;
+            let byte = line.index(bit_offset / 8)?;
+            let shift = 8 - bit_depth - bit_offset % 8;
+            Some(((byte >> shift) as u16) & max_sample(bit_depth as u8))
+        }
+    }
+}
+
+fn write_sample(line: &ScanLine, sample_index: usize, value: u16) {
+    let bit_depth = line.bit_depth() as usize;
+    match bit_depth {
+        16 => {
+            line.update(sample_index * 2, (value >> 8) as u8);
+            line.update(sample_index * 2 + 1, value as u8);
+        }
+        8 => line.update(sample_index, value as u8),
+        _ => {
+            let bit_offset = sample_index * bit_depth;
+            if let Some(byte) = line.index(bit_offset / 8) {
+                let shift = 8 - bit_depth - bit_offset % 8;
+                let mask = (max_sample(bit_depth as u8) as u8) << shift;
+                let value = ((value as u8) << shift) & mask;
+                line.update(bit_offset / 8, (byte & !mask) | value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::png::share_decoded_data;
+    use crate::FilterType;
+
+    fn scan_line(data: Vec<u8>, color_type: ColorType, bit_depth: u8, width: u32) -> ScanLine {
+        let range = 0..data.len();
+        ScanLine::new(FilterType::None, share_decoded_data(data), range, color_type, bit_depth, width)
+    }
+
+    #[test]
+    fn test_read_packed_pixels() {
+        let line = scan_line(vec![0, 0b1001_1100], ColorType::GrayScale, 2, 3);
+        assert_eq!(Some(Pixel::new(&[2])), read(&line, 0));
+        assert_eq!(Some(Pixel::new(&[1])), read(&line, 1));
+        assert_eq!(Some(Pixel::new(&[3])), read(&line, 2));
+        assert_eq!(None, read(&line, 3));
+    }
+
+    #[test]
+    fn test_write_packed_pixels() {
+        let line = scan_line(vec![0, 0b1001_1100], ColorType::GrayScale, 2, 3);
+        write(&line, 1, &Pixel::new(&[2]));
+        assert_eq!(Some(0b1010_1100), line.index(0));
+    }
+
+    #[test]
+    fn test_read_and_write_16bit_pixels() {
+        let line = scan_line(vec![0, 1, 2, 3, 4, 5, 6], ColorType::TrueColor, 16, 1);
+        assert_eq!(Some(Pixel::new(&[0x0102, 0x0304, 0x0506])), read(&line, 0));
+
+        write(&line, 0, &Pixel::new(&[0xABCD, 0, 0xFFFF]));
+        assert_eq!(Some(Pixel::new(&[0xABCD, 0, 0xFFFF])), read(&line, 0));
+    }
+}
@@ -0,0 +1,286 @@
+use std::io::Read;
+
+use png_glitch::{
+    AudioEffect, Axis, BitOperation, BitwiseOptions, BlockOperation, BlockOptions, ChannelOffset, ChannelShiftOptions, ColorType,
+    ConvertOptions, DatabendOptions, DisplaceOptions, Displacement, FilterStrategy, FilterType, Length, Pixel, PngGlitch, Region,
+    ResidualOperation, ResidualOptions, Rotation, SortDirection, SortOptions,
+};
+use proptest::prelude::*;
+
+const SIGNATURE: &[u8] = &[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+
+const FILTER_TYPES: [FilterType; 5] = [
+    FilterType::None,
+    FilterType::Sub,
+    FilterType::Up,
+    FilterType::Average,
+    FilterType::Paeth,
+];
+
+#[derive(Clone, Debug)]
+struct Image {
+    width: u32,
+    height: u32,
+    color_type: u8,
+    bit_depth: u8,
+    lines: Vec<Vec<u8>>,
+}
+
+impl Image {
+    fn encode(&self) -> Vec<u8> {
+        let mut ihdr = vec![];
+        ihdr.extend_from_slice(&self.width.to_be_bytes());
+        ihdr.extend_from_slice(&self.height.to_be_bytes());
+        ihdr.extend_from_slice(&[self.bit_depth, self.color_type, 0, 0, 0]);
+        let data: Vec<u8> = self.lines.concat();
+
+        let mut png = SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &ihdr);
+        if self.color_type == 3 {
+            let palette: Vec<u8> = (0..=255u8).flat_map(|index| [index, 255 - index, index / 2]).collect();
+            write_chunk(&mut png, b"PLTE", &palette);
+        }
+        write_chunk(&mut png, b"IDAT", &fdeflate::compress_to_vec(&data));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+fn write_chunk(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(chunk_type);
+    crc.update(data);
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(chunk_type);
+    png.extend_from_slice(data);
+    png.extend_from_slice(&crc.finalize().to_be_bytes());
+}
+
+fn bit_per_pixel(color_type: u8, bit_depth: u8) -> usize {
+    let channels = match color_type {
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => 1,
+    };
+    channels * bit_depth as usize
+}
+
+fn color_type_and_bit_depth() -> impl Strategy<Value = (u8, u8)> {
+    prop_oneof![
+        prop::sample::select(vec![1u8, 2, 4, 8, 16]).prop_map(|bit_depth| (0, bit_depth)),
+        prop::sample::select(vec![8u8, 16]).prop_map(|bit_depth| (2, bit_depth)),
+        prop::sample::select(vec![1u8, 2, 4, 8]).prop_map(|bit_depth| (3, bit_depth)),
+        prop::sample::select(vec![8u8, 16]).prop_map(|bit_depth| (4, bit_depth)),
+        prop::sample::select(vec![8u8, 16]).prop_map(|bit_depth| (6, bit_depth)),
+    ]
+}
+
+/// Images with random pixels, each scan line of which has a random filter type.
+fn image() -> impl Strategy<Value = Image> {
+    (color_type_and_bit_depth(), 1u32..24, 1u32..24).prop_flat_map(|((color_type, bit_depth), width, height)| {
+        let size = (bit_per_pixel(color_type, bit_depth) * width as usize).div_ceil(8);
+        let line = (0u8..5, prop::collection::vec(any::<u8>(), size)).prop_map(|(filter_type, pixels)| {
+            let mut line = vec![filter_type];
+            line.extend(pixels);
+            line
+        });
+        prop::collection::vec(line, height as usize).prop_map(move |lines| Image {
+            width,
+            height,
+            color_type,
+            bit_depth,
+            lines,
+        })
+    })
+}
+
+/// Rewrites CRC of each chunk, so that broken content reaches the later stages of parsing.
+fn fix_crc(bytes: &mut [u8]) {
+    let mut index = SIGNATURE.len();
+    while index + 12 <= bytes.len() {
+        let length = u32::from_be_bytes([bytes[index], bytes[index + 1], bytes[index + 2], bytes[index + 3]]) as usize;
+        let end = match (index + 8).checked_add(length) {
+            Some(end) if end + 4 <= bytes.len() => end,
+            _ => return,
+        };
+        let crc = crc32fast::hash(&bytes[index + 4..end]).to_be_bytes();
+        bytes[end..end + 4].copy_from_slice(&crc);
+        index = end + 4;
+    }
+}
+
+/// Valid images with some bytes overwritten and the tail possibly cut off.
+fn broken_image() -> impl Strategy<Value = Vec<u8>> {
+    let edits = prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..8);
+    let flags = (any::<bool>(), any::<bool>());
+    (image(), edits, any::<prop::sample::Index>(), flags).prop_map(|(image, edits, length, (crc, truncate))| {
+        let mut bytes = image.encode();
+        for (index, value) in edits {
+            let index = index.index(bytes.len());
+            bytes[index] = value;
+        }
+        if crc {
+            fix_crc(&mut bytes);
+        }
+        if truncate {
+            let length = length.index(bytes.len());
+            bytes.truncate(length);
+        }
+        bytes
+    })
+}
+
+fn scan_line_bytes(png_glitch: &PngGlitch) -> Vec<Vec<u8>> {
+    png_glitch
+        .scan_lines()
+        .into_iter()
+        .map(|mut scan_line| {
+            let mut bytes = vec![scan_line.filter_type().into()];
+            scan_line.read_to_end(&mut bytes).expect("Scan line should be read");
+            bytes
+        })
+        .collect()
+}
+
+fn pixels(png_glitch: &PngGlitch) -> Vec<Vec<Pixel>> {
+    png_glitch.scan_lines().iter().map(|line| line.pixels()).collect()
+}
+
+fn glitch(png_glitch: &mut PngGlitch) -> anyhow::Result<()> {
+    let width = png_glitch.width();
+    let height = png_glitch.height();
+    png_glitch.scan_lines();
+    png_glitch.transpose(1, 0, height);
+    png_glitch.remove_filter_from(height / 2, height);
+    png_glitch.apply_filter_from(FilterType::Paeth, height / 3, height);
+    png_glitch.apply_filter_strategy(&FilterStrategy::MinimumSumOfAbsoluteDifferences, 0, height);
+    png_glitch.sort(&SortOptions {
+        direction: SortDirection::Column,
+        ..SortOptions::default()
+    });
+    png_glitch.shift_channels(&ChannelShiftOptions::new(vec![ChannelOffset::new(3, 5, -2)]));
+    png_glitch.displace(&DisplaceOptions::new(Displacement::Constant(-3)));
+    png_glitch.move_block(&BlockOptions::new(BlockOperation::Smear, Region::new(1, 1, width, 2), 3, 4));
+    png_glitch.bitwise(&BitwiseOptions::new(BitOperation::RotateLeft(3)));
+    png_glitch.databend(&DatabendOptions::new(AudioEffect::Reverb {
+        room_size: Length::Pixels(3),
+        decay: 0.7,
+        mix: 0.5,
+    }));
+    png_glitch.edit_residuals(&ResidualOptions::new(FilterType::Up, ResidualOperation::Multiply(-2.5)));
+    let mut buffer = vec![];
+    png_glitch.encode(&mut buffer)?;
+    PngGlitch::new(buffer)?;
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn test_scan_lines_follow_header(image in image()) {
+        let png_glitch = PngGlitch::new(image.encode()).unwrap();
+        prop_assert_eq!(image.width, png_glitch.width());
+        prop_assert_eq!(image.height, png_glitch.height());
+        prop_assert_eq!(ColorType::try_from(image.color_type).ok(), png_glitch.scan_lines().first().map(|line| line.color_type()));
+        prop_assert_eq!(image.lines, scan_line_bytes(&png_glitch));
+    }
+
+    #[test]
+    fn test_remove_filter_inverts_apply_filter(image in image()) {
+        let mut png_glitch = PngGlitch::new(image.encode()).unwrap();
+        png_glitch.remove_filter();
+        let unfiltered = scan_line_bytes(&png_glitch);
+        for filter_type in FILTER_TYPES {
+            png_glitch.apply_filter(filter_type);
+            prop_assert!(png_glitch.scan_lines().iter().all(|line| line.filter_type() == filter_type));
+            png_glitch.remove_filter();
+            prop_assert_eq!(&unfiltered, &scan_line_bytes(&png_glitch));
+        }
+    }
+
+    #[test]
+    fn test_parse_encoded_png(image in image()) {
+        let png_glitch = PngGlitch::new(image.encode()).unwrap();
+        let mut encoded = vec![];
+        png_glitch.encode(&mut encoded).unwrap();
+        let parsed = PngGlitch::new(encoded.clone()).unwrap();
+        prop_assert_eq!(png_glitch.width(), parsed.width());
+        prop_assert_eq!(png_glitch.height(), parsed.height());
+        prop_assert_eq!(scan_line_bytes(&png_glitch), scan_line_bytes(&parsed));
+
+        let mut reencoded = vec![];
+        parsed.encode(&mut reencoded).unwrap();
+        prop_assert_eq!(encoded, reencoded);
+    }
+
+    #[test]
+    fn test_no_panic_on_arbitrary_bytes(bytes in prop::collection::vec(any::<u8>(), 0..256), signature in any::<bool>()) {
+        let bytes = if signature { [SIGNATURE, &bytes].concat() } else { bytes };
+        if let Ok(mut png_glitch) = PngGlitch::new(bytes) {
+            let _ = glitch(&mut png_glitch);
+        }
+    }
+
+    #[test]
+    fn test_no_panic_on_broken_image(bytes in broken_image()) {
+        if let Ok(mut png_glitch) = PngGlitch::new(bytes) {
+            let _ = glitch(&mut png_glitch);
+        }
+    }
+
+    #[test]
+    fn test_no_panic_on_glitching(image in image()) {
+        let mut png_glitch = PngGlitch::new(image.encode()).unwrap();
+        prop_assert!(glitch(&mut png_glitch).is_ok());
+    }
+
+    #[test]
+    fn test_convert_round_trip(image in image().prop_filter("Palettes are quantized again", |image| image.color_type != 3)) {
+        let mut original = PngGlitch::new(image.encode()).unwrap();
+        let mut converted = original.fork();
+        converted.convert(&ConvertOptions::new(ColorType::TrueColorAlpha, 16)).unwrap();
+        converted.convert(&ConvertOptions::new(original.color_type(), original.bit_depth())).unwrap();
+        let filter_types = |png_glitch: &PngGlitch| -> Vec<FilterType> {
+            png_glitch.scan_lines().iter().map(|line| line.filter_type()).collect()
+        };
+        prop_assert_eq!(filter_types(&original), filter_types(&converted));
+
+        // Padding bits at the end of scan lines are not kept, so that pixels are compared without filters.
+        original.remove_filter();
+        converted.remove_filter();
+        prop_assert_eq!(pixels(&original), pixels(&converted));
+    }
+
+    #[test]
+    fn test_rotations_and_flips_round_trip(image in image()) {
+        let mut original = PngGlitch::new(image.encode()).unwrap();
+        let mut transformed = original.fork();
+        for rotation in [Rotation::Clockwise, Rotation::HalfTurn, Rotation::Clockwise] {
+            transformed.rotate(rotation).unwrap();
+        }
+        transformed.flip(Axis::Horizontal).unwrap();
+        transformed.flip(Axis::Vertical).unwrap();
+        transformed.rotate(Rotation::HalfTurn).unwrap();
+        prop_assert_eq!((original.width(), original.height()), (transformed.width(), transformed.height()));
+
+        original.remove_filter();
+        transformed.remove_filter();
+        prop_assert_eq!(pixels(&original), pixels(&transformed));
+    }
+
+    #[test]
+    fn test_undo_reverts_glitching(image in image()) {
+        let mut png_glitch = PngGlitch::new(image.encode()).unwrap();
+        let original = png_glitch.snapshot();
+        png_glitch.enable_history(usize::MAX);
+        glitch(&mut png_glitch).unwrap();
+        let glitched = png_glitch.snapshot();
+
+        while png_glitch.undo() {}
+        prop_assert_eq!(&original, &png_glitch.snapshot());
+        while png_glitch.redo() {}
+        prop_assert_eq!(&glitched, &png_glitch.snapshot());
+    }
+}
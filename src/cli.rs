@@ -1,14 +1,119 @@
-use clap::{Parser, Subcommand};
+use crate::target::{parse_region, Position, Target};
+use clap::{Args, Command, CommandFactory, Parser, Subcommand, ValueEnum};
+use png_glitch::{Axis, FilterType, Pixel, Region, Rotation};
+use std::ffi::OsString;
+
+pub const STEP_SEPARATOR: &str = "then";
 
 #[derive(Parser, Debug)]
+#[command(
+    about,
+    subcommand_negates_reqs = true,
+    after_help = "Subcommands can be chained with `then`, such as `png-glitch input.png unfilter then transpose -d 50% --lines ..10%`"
+)]
 pub struct Cli {
     #[arg(short, default_value = "glitched.png")]
     pub output_file: String,
-    pub png_file: String,
+    #[arg(required = true)]
+    pub png_file: Option<String>,
+    /// Writes the given number of frames as a numbered PNG sequence, such as glitched-0000.png.
+    #[arg(long)]
+    pub frames: Option<u32>,
+    /// Glitches each frame on top of the previous one, instead of the original image.
+    #[arg(long, requires = "frames")]
+    pub accumulate: bool,
+    /// Reads the PNG file even if the CRC of its chunks does not match, such as after editing it with a hex editor.
+    #[arg(long)]
+    pub ignore_crc: bool,
+
+    #[command(flatten)]
+    pub target: Target,
 
     #[command(subcommand)]
-    pub sub_command: Option<GlitchStrategy>,
+    pub sub_command: Option<SubCommand>,
+
+    /// The subcommands chained after the first one.
+    #[arg(skip)]
+    pub steps: Vec<Step>,
 }
+
+// Step is a subcommand chained after another one with `then`. Each step takes its own target.
+#[derive(Parser, Debug)]
+#[command(name = STEP_SEPARATOR, no_binary_name = true, about = "Runs a subcommand after the previous one")]
+pub struct Step {
+    #[command(flatten)]
+    pub target: Target,
+
+    #[command(subcommand)]
+    pub strategy: GlitchStrategy,
+}
+
+/// Splits the arguments into the ones of the first subcommand and the ones of each step chained with `then`.
+/// `then` is taken as a value when it follows an option which takes a value, such as `-o then`.
+pub fn split_steps(args: &[OsString]) -> Vec<&[OsString]> {
+    let mut cli = Cli::command();
+    let mut step = Step::command();
+    cli.build();
+    step.build();
+    let mut command = &cli;
+    let mut segments = vec![];
+    let mut start = 0;
+    let mut is_value = false;
+    for (index, arg) in args.iter().enumerate().skip(1) {
+        let Some(arg) = arg.to_str().filter(|_| !is_value) else {
+            is_value = false;
+            continue;
+        };
+        if arg == STEP_SEPARATOR {
+            segments.push(&args[start..index]);
+            start = index + 1;
+            command = &step;
+        } else if let Some(sub_command) = command.find_subcommand(arg) {
+            command = sub_command;
+        } else {
+            is_value = takes_value(command, arg);
+        }
+    }
+    segments.push(&args[start..]);
+    segments
+}
+
+/// Returns true if the argument is an option of the command which takes the next argument as its value.
+fn takes_value(command: &Command, arg: &str) -> bool {
+    let matches = |option: &clap::Arg| {
+        if let Some(long) = arg.strip_prefix("--") {
+            option.get_long_and_visible_aliases().is_some_and(|names| names.contains(&long))
+        } else {
+            let mut chars = arg.chars();
+            match (chars.next(), chars.next(), chars.next()) {
+                (Some('-'), Some(short), None) => option.get_short_and_visible_aliases().is_some_and(|names| names.contains(&short)),
+                _ => false,
+            }
+        }
+    };
+    command
+        .get_arguments()
+        .filter(|option| !option.is_positional() && option.get_action().takes_values())
+        .any(matches)
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SubCommand {
+    /// Compares two images of the same size, and reports the scan lines and bytes which differ.
+    Diff(DiffArgs),
+    #[command(flatten)]
+    Glitch(GlitchStrategy),
+}
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    pub original: String,
+    pub glitched: String,
+    /// Saves a heatmap of the differences as a PNG file.
+    #[arg(long)]
+    pub heatmap: Option<String>,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum GlitchStrategy {
     Substitute {
@@ -16,9 +121,229 @@ pub enum GlitchStrategy {
         index: usize,
         #[clap(short, default_value = "0")]
         value: u8,
+        /// Increases the value by the given amount in each frame.
+        #[clap(long, default_value = "0")]
+        step: u8,
     },
     RandomCopy {
         #[clap(short, default_value = "1")]
         times: u32,
+        /// Increases the number of copies by the given amount in each frame.
+        #[clap(long, default_value = "0")]
+        step: u32,
+    },
+    BitFlip {
+        /// The probability to flip each bit, from 0 to 1.
+        #[clap(short, default_value = "0.001", value_parser = parse_probability)]
+        probability: f64,
+        #[clap(short)]
+        seed: Option<u64>,
+        #[clap(short, long)]
+        unfiltered: bool,
+    },
+    Xor {
+        #[clap(short, default_value = "255")]
+        mask: u8,
+        #[clap(short, long)]
+        unfiltered: bool,
+    },
+    And {
+        #[clap(short, default_value = "255")]
+        mask: u8,
+        #[clap(short, long)]
+        unfiltered: bool,
+    },
+    Or {
+        #[clap(short, default_value = "0")]
+        mask: u8,
+        #[clap(short, long)]
+        unfiltered: bool,
+    },
+    RotateBits {
+        #[clap(short, default_value = "1")]
+        bits: u32,
+        #[clap(short, long)]
+        right: bool,
+        #[clap(short, long)]
+        unfiltered: bool,
+    },
+    SwapBitPlanes {
+        #[clap(short, default_value = "0")]
+        a: u32,
+        #[clap(short, default_value = "7")]
+        b: u32,
+        #[clap(short, long)]
+        unfiltered: bool,
+    },
+    Crush {
+        #[clap(short, default_value = "4")]
+        bits: u32,
+        #[clap(short, long)]
+        unfiltered: bool,
+    },
+    /// Applies the filter to the scan lines, treating their bytes as unfiltered ones.
+    Filter {
+        #[arg(value_enum)]
+        filter_type: FilterTypeArg,
+    },
+    /// Removes filters from the scan lines.
+    Unfilter,
+    /// Swaps the scan lines with the ones starting from the destination.
+    Transpose {
+        /// The first scan line of the destination, such as `120` or `50%`.
+        #[clap(short, long)]
+        dest: Position,
+    },
+    /// Rotates the whole image, so that the following glitches on scan lines run vertically. Targets are not accepted.
+    Rotate {
+        #[arg(value_enum)]
+        rotation: RotationArg,
+    },
+    /// Flips the whole image. Targets are not accepted.
+    Flip {
+        #[arg(value_enum)]
+        axis: AxisArg,
+    },
+    /// Crops the image to the rectangle specified as `x,y,width,height`. Targets are not accepted.
+    Crop {
+        #[arg(value_parser = parse_region)]
+        region: Region,
+    },
+    /// Adds margins around the image. Targets are not accepted.
+    Pad {
+        /// The margin on every side, in pixels.
+        margin: u32,
+        #[arg(long)]
+        top: Option<u32>,
+        #[arg(long)]
+        right: Option<u32>,
+        #[arg(long)]
+        bottom: Option<u32>,
+        #[arg(long)]
+        left: Option<u32>,
+        /// The samples of the pixel filling the margins, such as `255,255,255` for white in 8-bit RGB.
+        #[arg(long, value_parser = parse_pixel)]
+        pixel: Option<Pixel>,
+    },
+    /// Resizes the image with the nearest neighbour interpolation. Targets are not accepted.
+    Resize {
+        width: u32,
+        height: u32,
+    },
+}
+
+fn parse_pixel(s: &str) -> Result<Pixel, String> {
+    let samples: Vec<u16> = s
+        .split(',')
+        .map(|sample| sample.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("`{}` is not a pixel such as `255,255,255`", s))?;
+    match samples.len() {
+        1..=4 => Ok(Pixel::new(&samples)),
+        _ => Err(format!("`{}` should have 1 to 4 samples", s)),
+    }
+}
+
+fn parse_probability(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(probability) if (0.0..=1.0).contains(&probability) => Ok(probability),
+        _ => Err(format!("`{}` is not a probability between 0 and 1", s)),
+    }
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug)]
+pub enum FilterTypeArg {
+    None,
+    Sub,
+    Up,
+    Average,
+    Paeth,
+}
+
+impl From<FilterTypeArg> for FilterType {
+    fn from(value: FilterTypeArg) -> Self {
+        match value {
+            FilterTypeArg::None => FilterType::None,
+            FilterTypeArg::Sub => FilterType::Sub,
+            FilterTypeArg::Up => FilterType::Up,
+            FilterTypeArg::Average => FilterType::Average,
+            FilterTypeArg::Paeth => FilterType::Paeth,
+        }
+    }
+}
+#[derive(ValueEnum, Copy, Clone, Debug)]
+pub enum RotationArg {
+    /// 90 degrees clockwise.
+    Cw,
+    /// 90 degrees counterclockwise.
+    Ccw,
+    /// 180 degrees.
+    #[value(name = "180")]
+    HalfTurn,
+}
+
+impl From<RotationArg> for Rotation {
+    fn from(value: RotationArg) -> Self {
+        match value {
+            RotationArg::Cw => Rotation::Clockwise,
+            RotationArg::Ccw => Rotation::CounterClockwise,
+            RotationArg::HalfTurn => Rotation::HalfTurn,
+        }
+    }
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug)]
+pub enum AxisArg {
+    /// Mirrors the image left to right.
+    Horizontal,
+    /// Turns the image upside down.
+    Vertical,
+}
+
+impl From<AxisArg> for Axis {
+    fn from(value: AxisArg) -> Self {
+        match value {
+            AxisArg::Horizontal => Axis::Horizontal,
+            AxisArg::Vertical => Axis::Vertical,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn split(args: &str) -> Vec<Vec<String>> {
+        let args: Vec<OsString> = args.split_whitespace().map(OsString::from).collect();
+        split_steps(&args)
+            .iter()
+            .map(|segment| segment.iter().map(|arg| arg.to_string_lossy().into_owned()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_split_steps() {
+        assert_eq!(
+            vec![vec!["png-glitch", "input.png", "unfilter"], vec!["transpose", "-d", "50%"]],
+            split("png-glitch input.png unfilter then transpose -d 50%")
+        );
+        assert_eq!(
+            vec![vec!["png-glitch", "input.png", "-o", "then", "unfilter"], vec!["filter", "--lines", "then"]],
+            split("png-glitch input.png -o then unfilter then filter --lines then")
+        );
+        assert_eq!(
+            vec![vec!["png-glitch", "input.png", "bit-flip", "-s", "then", "-u"], vec!["unfilter"]],
+            split("png-glitch input.png bit-flip -s then -u then unfilter")
+        );
+    }
+
+    #[test]
+    fn test_output_file_named_then() {
+        let args: Vec<OsString> = "png-glitch input.png -o then unfilter then filter".split_whitespace().map(OsString::from).collect();
+        let segments = split_steps(&args);
+        let cli = Cli::parse_from(segments[0]);
+        assert_eq!("then", cli.output_file);
+        assert!(matches!(cli.sub_command, Some(SubCommand::Glitch(GlitchStrategy::Unfilter))));
+        assert_eq!(1, segments[1..].len());
     }
-}
\ No newline at end of file
+}
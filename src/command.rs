@@ -1,10 +1,25 @@
 mod substitute;
 mod random_copy;
+mod bitwise;
+mod filter;
+mod transpose;
+mod geometry;
 
 use png_glitch::PngGlitch;
+use std::ops::Range;
 pub use substitute::Substitute;
 pub use random_copy::RandomCopy;
+pub use bitwise::Bitwise;
+pub use filter::{Filter, Unfilter};
+pub use transpose::Transpose;
+pub use geometry::{Crop, Flip, Pad, Resize, Rotate};
 
 pub trait Command {
-    fn run(&self, png: &mut PngGlitch);
+    /// Glitches the given runs of scan lines. `frame` is the index of the frame being generated, which is 0 for a single image.
+    fn run(&self, png: &mut PngGlitch, lines: &[Range<u32>], frame: u32) -> anyhow::Result<()>;
+
+    /// Returns false if the command transforms the whole image, so that targets can not be specified for it.
+    fn accepts_target(&self) -> bool {
+        true
+    }
 }
\ No newline at end of file
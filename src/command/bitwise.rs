@@ -0,0 +1,42 @@
+use crate::command::Command;
+use png_glitch::{BitOperation, BitwiseOptions, Domain, PngGlitch};
+use std::ops::Range;
+
+pub struct Bitwise {
+    operation: BitOperation,
+    domain: Domain,
+}
+
+impl Bitwise {
+    pub fn new(operation: BitOperation, unfiltered: bool) -> Bitwise {
+        let domain = if unfiltered {
+            Domain::Unfiltered
+        } else {
+            Domain::Filtered
+        };
+        Bitwise { operation, domain }
+    }
+}
+
+impl Command for Bitwise {
+    fn run(&self, png: &mut PngGlitch, lines: &[Range<u32>], frame: u32) -> anyhow::Result<()> {
+        for range in lines {
+            // Each frame and run flips other bits, otherwise accumulated frames would flip the same bits back.
+            let operation = match self.operation {
+                BitOperation::Flip { probability, seed } => BitOperation::Flip {
+                    probability,
+                    seed: seed.wrapping_add(frame as u64).wrapping_add((range.start as u64) << 32),
+                },
+                operation => operation,
+            };
+            let options = BitwiseOptions {
+                domain: self.domain,
+                from: range.start,
+                lines: range.len() as u32,
+                ..BitwiseOptions::new(operation)
+            };
+            png.bitwise(&options);
+        }
+        Ok(())
+    }
+}
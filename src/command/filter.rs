@@ -0,0 +1,33 @@
+use crate::command::Command;
+use png_glitch::{FilterType, PngGlitch};
+use std::ops::Range;
+
+pub struct Filter {
+    filter_type: FilterType,
+}
+
+impl Filter {
+    pub fn new(filter_type: FilterType) -> Filter {
+        Filter { filter_type }
+    }
+}
+
+impl Command for Filter {
+    fn run(&self, png: &mut PngGlitch, lines: &[Range<u32>], _frame: u32) -> anyhow::Result<()> {
+        for range in lines {
+            png.apply_filter_from(self.filter_type, range.start, range.len() as u32);
+        }
+        Ok(())
+    }
+}
+
+pub struct Unfilter;
+
+impl Command for Unfilter {
+    fn run(&self, png: &mut PngGlitch, lines: &[Range<u32>], _frame: u32) -> anyhow::Result<()> {
+        for range in lines {
+            png.remove_filter_from(range.start, range.len() as u32);
+        }
+        Ok(())
+    }
+}
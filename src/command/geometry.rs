@@ -0,0 +1,114 @@
+use crate::command::Command;
+use png_glitch::{Axis, PadOptions, PngGlitch, Region, Rotation};
+use std::ops::Range;
+
+pub struct Rotate {
+    rotation: Rotation,
+}
+
+impl Rotate {
+    pub fn new(rotation: Rotation) -> Rotate {
+        Rotate { rotation }
+    }
+}
+
+impl Command for Rotate {
+    /// Rotates the whole image.
+    fn run(&self, png: &mut PngGlitch, _lines: &[Range<u32>], _frame: u32) -> anyhow::Result<()> {
+        png.rotate(self.rotation)?;
+        Ok(())
+    }
+
+    fn accepts_target(&self) -> bool {
+        false
+    }
+}
+
+pub struct Flip {
+    axis: Axis,
+}
+
+impl Flip {
+    pub fn new(axis: Axis) -> Flip {
+        Flip { axis }
+    }
+}
+
+impl Command for Flip {
+    /// Flips the whole image.
+    fn run(&self, png: &mut PngGlitch, _lines: &[Range<u32>], _frame: u32) -> anyhow::Result<()> {
+        png.flip(self.axis)?;
+        Ok(())
+    }
+
+    fn accepts_target(&self) -> bool {
+        false
+    }
+}
+
+pub struct Crop {
+    region: Region,
+}
+
+impl Crop {
+    pub fn new(region: Region) -> Crop {
+        Crop { region }
+    }
+}
+
+impl Command for Crop {
+    /// Crops the whole image to the region.
+    fn run(&self, png: &mut PngGlitch, _lines: &[Range<u32>], _frame: u32) -> anyhow::Result<()> {
+        png.crop(&self.region)?;
+        Ok(())
+    }
+
+    fn accepts_target(&self) -> bool {
+        false
+    }
+}
+
+pub struct Pad {
+    options: PadOptions,
+}
+
+impl Pad {
+    pub fn new(options: PadOptions) -> Pad {
+        Pad { options }
+    }
+}
+
+impl Command for Pad {
+    /// Adds margins around the whole image.
+    fn run(&self, png: &mut PngGlitch, _lines: &[Range<u32>], _frame: u32) -> anyhow::Result<()> {
+        png.pad(&self.options)?;
+        Ok(())
+    }
+
+    fn accepts_target(&self) -> bool {
+        false
+    }
+}
+
+pub struct Resize {
+    width: u32,
+    height: u32,
+}
+
+impl Resize {
+    pub fn new(width: u32, height: u32) -> Resize {
+        Resize { width, height }
+    }
+}
+
+impl Command for Resize {
+    /// Resizes the whole image.
+    fn run(&self, png: &mut PngGlitch, _lines: &[Range<u32>], _frame: u32) -> anyhow::Result<()> {
+        png.resize(self.width, self.height)?;
+        Ok(())
+    }
+
+    fn accepts_target(&self) -> bool {
+        false
+    }
+}
@@ -2,34 +2,45 @@ use crate::command::Command;
 use png_glitch::PngGlitch;
 use rand::{thread_rng, Rng};
 use std::io::{Read, Write};
+use std::ops::Range;
 
 pub struct RandomCopy {
     times: u32,
+    step: u32,
 }
 
 impl RandomCopy {
-    pub fn new(times: u32) -> RandomCopy {
-        RandomCopy { times }
+    /// The number of copies increases by `step` in each frame.
+    pub fn new(times: u32, step: u32) -> RandomCopy {
+        RandomCopy { times, step }
     }
 }
 
 impl Command for RandomCopy {
-    fn run(&self, png: &mut PngGlitch) {
-        let mut scan_lines = png.scan_lines();
+    fn run(&self, png: &mut PngGlitch, lines: &[Range<u32>], frame: u32) -> anyhow::Result<()> {
+        let mut scan_lines: Vec<_> = lines
+            .iter()
+            .flat_map(|range| png.scan_lines_from(range.start, range.len() as u32))
+            .collect();
+        if scan_lines.is_empty() {
+            return Ok(());
+        }
         let mut rng = thread_rng();
         let index_range = 0..scan_lines.len();
-        for _ in 0..self.times {
+        let times = self.times.saturating_add(self.step.saturating_mul(frame));
+        for _ in 0..times {
             let src = rng.gen_range(index_range.clone());
             let dest = rng.gen_range(index_range.clone());
 
             let src = &mut scan_lines[src];
             let filter_type = src.filter_type();
             let mut buffer = vec![];
-            src.read_to_end(&mut buffer).unwrap();
+            src.read_to_end(&mut buffer)?;
 
             let dest = &mut scan_lines[dest];
-            dest.write(&buffer).unwrap();
+            dest.write_all(&buffer)?;
             dest.set_filter_type(filter_type);
         }
+        Ok(())
     }
 }
\ No newline at end of file
@@ -1,23 +1,31 @@
 use crate::command::Command;
 use png_glitch::PngGlitch;
+use std::ops::Range;
+
 #[derive(Debug, Default)]
 pub struct Substitute {
     index: usize,
     value: u8,
+    step: u8,
 }
 
 impl Substitute {
-    pub fn new(index: usize, value: u8) -> Substitute {
-        Substitute { index, value }
+    /// The value increases by `step` in each frame, wrapping around at 255.
+    pub fn new(index: usize, value: u8, step: u8) -> Substitute {
+        Substitute { index, value, step }
     }
 }
 
 impl Command for Substitute {
-    fn run(&self, png: &mut PngGlitch) {
-        png.foreach_scanline(|scanline| {
-            if self.index < scanline.size() {
-                scanline[self.index] = self.value;
+    fn run(&self, png: &mut PngGlitch, lines: &[Range<u32>], frame: u32) -> anyhow::Result<()> {
+        let value = self.value.wrapping_add(self.step.wrapping_mul(frame as u8));
+        for range in lines {
+            for mut scanline in png.scan_lines_from(range.start, range.len() as u32) {
+                if self.index < scanline.size() {
+                    scanline[self.index] = value;
+                }
             }
-        });
+        }
+        Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,29 @@
+use crate::command::Command;
+use crate::target::Position;
+use png_glitch::PngGlitch;
+use std::ops::Range;
+
+pub struct Transpose {
+    dest: Position,
+}
+
+impl Transpose {
+    pub fn new(dest: Position) -> Transpose {
+        Transpose { dest }
+    }
+}
+
+impl Command for Transpose {
+    /// Swaps the targeted scan lines with the ones starting from the destination, keeping their spacing.
+    fn run(&self, png: &mut PngGlitch, lines: &[Range<u32>], _frame: u32) -> anyhow::Result<()> {
+        let Some(first) = lines.first() else {
+            return Ok(());
+        };
+        let dest = self.dest.resolve(png.height());
+        for range in lines {
+            let offset = range.start - first.start;
+            png.transpose(range.start, dest.saturating_add(offset), range.len() as u32);
+        }
+        Ok(())
+    }
+}
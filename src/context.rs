@@ -1,52 +1,169 @@
-use crate::cli::{Cli, GlitchStrategy};
-use crate::command::{Command, RandomCopy, Substitute};
-use png_glitch::PngGlitch;
+use crate::cli::{Cli, GlitchStrategy, SubCommand};
+use crate::command::{Bitwise, Command, Crop, Filter, Flip, Pad, RandomCopy, Resize, Rotate, Substitute, Transpose, Unfilter};
+use crate::target::Target;
+use anyhow::anyhow;
+use png_glitch::{BitOperation, PadOptions, ParseOptions, PngGlitch};
+use std::path::Path;
+
+type Step = (Box<dyn Command>, Target);
 
 pub struct Context {
-    command: Box<dyn Command>,
+    steps: Vec<Step>,
     png_glitch: PngGlitch,
     output_file: String,
+    frames: Option<u32>,
+    accumulate: bool,
 }
 
 impl Context {
     pub fn start(&mut self) -> anyhow::Result<()> {
-        self.command.run(&mut self.png_glitch);
-        self.png_glitch.save(&self.output_file)?;
+        let Some(frames) = self.frames else {
+            return glitch(&self.steps, &mut self.png_glitch, 0, &self.output_file);
+        };
+        let mut previous = self.png_glitch.fork();
+        for frame in 0..frames {
+            let mut png_glitch = if self.accumulate {
+                previous
+            } else {
+                self.png_glitch.fork()
+            };
+            glitch(&self.steps, &mut png_glitch, frame, &frame_file_name(&self.output_file, frame))?;
+            previous = png_glitch;
+        }
         Ok(())
     }
 }
 
+/// Runs the steps to generate the frame, and saves it.
+fn glitch(steps: &[Step], png_glitch: &mut PngGlitch, frame: u32, output_file: &str) -> anyhow::Result<()> {
+    for (command, target) in steps {
+        let lines = target.scan_lines(png_glitch.height(), frame);
+        match target.mask(png_glitch.width(), png_glitch.height(), frame) {
+            Some(mask) => {
+                let mut result = Ok(());
+                png_glitch.with_mask(&mask, |png_glitch| result = command.run(png_glitch, &lines, frame));
+                result?
+            }
+            None => command.run(png_glitch, &lines, frame)?,
+        }
+    }
+    png_glitch.save(output_file)?;
+    Ok(())
+}
+
+/// Inserts the frame number before the extension, such as `glitched-0001.png`.
+fn frame_file_name(output_file: &str, frame: u32) -> String {
+    let path = Path::new(output_file);
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let extension = path.extension().map(|extension| extension.to_string_lossy()).unwrap_or("png".into());
+    let file_name = format!("{}-{:04}.{}", stem, frame, extension);
+    path.with_file_name(file_name).to_string_lossy().into_owned()
+}
+
 impl TryFrom<Cli> for Context {
     type Error = anyhow::Error;
 
     fn try_from(cli: Cli) -> Result<Self, Self::Error> {
-        let png_glitch = PngGlitch::open(cli.png_file)?;
-        let command = create_command(cli.sub_command);
+        let png_file = cli.png_file.ok_or(anyhow!("A PNG file to glitch is required"))?;
+        let options = ParseOptions {
+            verify_crc: !cli.ignore_crc,
+            ..ParseOptions::default()
+        };
+        let png_glitch = PngGlitch::open_with_options(png_file, &options)?;
+        let mut steps = vec![(create_command(cli.sub_command), cli.target)];
+        for step in cli.steps {
+            steps.push((create_command_from_glitch_strategy(step.strategy), step.target));
+        }
+        if steps.iter().any(|(command, target)| !command.accepts_target() && target.is_specified()) {
+            return Err(anyhow!(
+                "rotate, flip, crop, pad, and resize transform the whole image, and do not accept --lines, --every, --rect, or --walk"
+            ));
+        }
         let output_file = cli.output_file;
 
         let context = Context {
             png_glitch,
-            command,
+            steps,
             output_file,
+            frames: cli.frames,
+            accumulate: cli.accumulate,
         };
         Ok(context)
     }
 }
 
-fn create_command(glitch_strategy: Option<GlitchStrategy>) -> Box<dyn Command> {
-    match glitch_strategy {
-        Some(strategy) => create_command_from_glitch_strategy(strategy),
-        None => Box::new(Substitute::default()),
+fn create_command(sub_command: Option<SubCommand>) -> Box<dyn Command> {
+    match sub_command {
+        Some(SubCommand::Glitch(strategy)) => create_command_from_glitch_strategy(strategy),
+        _ => Box::new(Substitute::default()),
     }
 }
 
 fn create_command_from_glitch_strategy(glitch_strategy: GlitchStrategy) -> Box<dyn Command> {
     match glitch_strategy {
-        GlitchStrategy::Substitute { index, value} => {
-            Box::new(Substitute::new(index, value))
+        GlitchStrategy::Substitute { index, value, step } => {
+            Box::new(Substitute::new(index, value, step))
+        }
+        GlitchStrategy::RandomCopy { times, step } => {
+            Box::new(RandomCopy::new(times, step))
+        }
+        GlitchStrategy::BitFlip { probability, seed, unfiltered } => {
+            let seed = seed.unwrap_or_else(rand::random);
+            Box::new(Bitwise::new(BitOperation::Flip { probability, seed }, unfiltered))
+        }
+        GlitchStrategy::Xor { mask, unfiltered } => {
+            Box::new(Bitwise::new(BitOperation::Xor(mask), unfiltered))
+        }
+        GlitchStrategy::And { mask, unfiltered } => {
+            Box::new(Bitwise::new(BitOperation::And(mask), unfiltered))
+        }
+        GlitchStrategy::Or { mask, unfiltered } => {
+            Box::new(Bitwise::new(BitOperation::Or(mask), unfiltered))
+        }
+        GlitchStrategy::RotateBits { bits, right, unfiltered } => {
+            let operation = if right {
+                BitOperation::RotateRight(bits)
+            } else {
+                BitOperation::RotateLeft(bits)
+            };
+            Box::new(Bitwise::new(operation, unfiltered))
+        }
+        GlitchStrategy::SwapBitPlanes { a, b, unfiltered } => {
+            Box::new(Bitwise::new(BitOperation::SwapPlanes(a, b), unfiltered))
+        }
+        GlitchStrategy::Crush { bits, unfiltered } => {
+            Box::new(Bitwise::new(BitOperation::Crush(bits), unfiltered))
+        }
+        GlitchStrategy::Filter { filter_type } => {
+            Box::new(Filter::new(filter_type.into()))
+        }
+        GlitchStrategy::Unfilter => {
+            Box::new(Unfilter)
+        }
+        GlitchStrategy::Transpose { dest } => {
+            Box::new(Transpose::new(dest))
+        }
+        GlitchStrategy::Rotate { rotation } => {
+            Box::new(Rotate::new(rotation.into()))
+        }
+        GlitchStrategy::Flip { axis } => {
+            Box::new(Flip::new(axis.into()))
+        }
+        GlitchStrategy::Crop { region } => {
+            Box::new(Crop::new(region))
+        }
+        GlitchStrategy::Pad { margin, top, right, bottom, left, pixel } => {
+            let options = PadOptions {
+                top: top.unwrap_or(margin),
+                right: right.unwrap_or(margin),
+                bottom: bottom.unwrap_or(margin),
+                left: left.unwrap_or(margin),
+                pixel: pixel.unwrap_or_default(),
+            };
+            Box::new(Pad::new(options))
         }
-        GlitchStrategy::RandomCopy { times } => {
-            Box::new(RandomCopy::new(times))
+        GlitchStrategy::Resize { width, height } => {
+            Box::new(Resize::new(width, height))
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,38 @@
+use crate::cli::DiffArgs;
+use crate::target::Target;
+use anyhow::anyhow;
+use png_glitch::PngGlitch;
+
+/// Reports the differences in the targeted scan lines, and saves the heatmap of the whole image.
+pub fn run(args: &DiffArgs, target: &Target) -> anyhow::Result<()> {
+    let original = PngGlitch::open(&args.original)?;
+    let glitched = PngGlitch::open(&args.glitched)?;
+    let difference = original
+        .diff(&glitched)
+        .ok_or(anyhow!("The images differ in their size, color type, or bit depth"))?;
+
+    println!(
+        "{} of {} scan lines differ: {} bytes in filtered data, {} bytes in unfiltered data",
+        difference.lines().len(),
+        original.height(),
+        difference.filtered_bytes(),
+        difference.unfiltered_bytes()
+    );
+    let targets = target.scan_lines(original.height(), 0);
+    let lines = difference
+        .lines()
+        .iter()
+        .filter(|line| targets.iter().any(|range| range.contains(&line.index)));
+    for line in lines {
+        let (original_filter_type, glitched_filter_type) = line.filter_types;
+        println!(
+            "#{}: filter type {:?} -> {:?}, filtered {:?}, unfiltered {:?}",
+            line.index, original_filter_type, glitched_filter_type, line.filtered, line.unfiltered
+        );
+    }
+
+    if let Some(heatmap) = &args.heatmap {
+        difference.heatmap()?.save(heatmap)?;
+    }
+    Ok(())
+}
@@ -1,20 +1,29 @@
 use clap::Parser;
+use std::ffi::OsString;
 
-use crate::cli::Cli;
+use crate::cli::{split_steps, Cli, Step, SubCommand};
 use crate::context::Context;
 
 mod cli;
 mod command;
 mod context;
+mod diff;
+mod target;
 
 fn main() {
-    let config = Cli::parse();
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let segments = split_steps(&args);
+    let mut config = Cli::parse_from(segments[0]);
+    config.steps = segments[1..].iter().map(|segment| Step::parse_from(*segment)).collect();
     if let Err(e) = start(config) {
         println!("{:?}", e);
     }
 }
 
 fn start(cli: Cli) -> anyhow::Result<()> {
+    if let Some(SubCommand::Diff(args)) = &cli.sub_command {
+        return diff::run(args, &cli.target);
+    }
     let mut context: Context = cli.try_into()?;
     context.start()
 }
\ No newline at end of file
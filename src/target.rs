@@ -0,0 +1,186 @@
+use clap::Args;
+use png_glitch::{Mask, Region};
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Target specifies the part of the image to glitch.
+#[derive(Args, Debug, Clone, Default)]
+pub struct Target {
+    /// Scan lines to glitch, such as `10..200` or `25%..75%`. The end is excluded, and either end can be omitted.
+    #[arg(long, global = true)]
+    pub lines: Option<LineRange>,
+    /// Glitches every n-th scan line in the target.
+    #[arg(long, global = true)]
+    pub every: Option<u32>,
+    /// Glitches the pixels in the rectangle specified as `x,y,width,height`.
+    #[arg(long, global = true, value_parser = parse_region)]
+    pub rect: Option<Region>,
+    /// Moves the target down by the given number of scan lines in each frame. Negative values move it up.
+    #[arg(long, global = true, allow_negative_numbers = true)]
+    pub walk: Option<i64>,
+}
+
+impl Target {
+    /// Returns true if any part of the image is targeted explicitly.
+    pub fn is_specified(&self) -> bool {
+        self.lines.is_some() || self.every.is_some() || self.rect.is_some() || self.walk.is_some()
+    }
+
+    /// Returns the runs of the targeted scan lines of the frame in an image of the given height.
+    pub fn scan_lines(&self, height: u32, frame: u32) -> Vec<Range<u32>> {
+        let range = self.lines.map_or(0..height, |lines| lines.resolve(height));
+        let range = match self.rect {
+            Some(rect) => {
+                let rect = rect.clip(u32::MAX, height);
+                range.start.max(rect.y)..range.end.min(rect.y + rect.height)
+            }
+            None => range,
+        };
+        let range = self.walk_to(range.start, frame).min(height)..self.walk_to(range.end, frame).min(height);
+        match self.every {
+            Some(every) if every > 1 => range.step_by(every as usize).map(|index| index..index + 1).collect(),
+            _ if range.is_empty() => vec![],
+            _ => vec![range],
+        }
+    }
+
+    /// Returns the mask selecting the pixels in the rectangle of the frame, if it is specified.
+    pub fn mask(&self, width: u32, height: u32, frame: u32) -> Option<Mask> {
+        self.rect.map(|rect| {
+            let top = self.walk_to(rect.y, frame);
+            let bottom = self.walk_to(rect.y.saturating_add(rect.height), frame);
+            Mask::rect(width, height, Region::new(rect.x, top, rect.width, bottom - top))
+        })
+    }
+
+    /// Returns the scan line which the given one moves to in the frame.
+    fn walk_to(&self, line: u32, frame: u32) -> u32 {
+        let offset = self.walk.unwrap_or(0).saturating_mul(frame as i64);
+        (line as i64).saturating_add(offset).clamp(0, u32::MAX as i64) as u32
+    }
+}
+
+/// LineRange is a range of scan lines, whose ends are given as line numbers or percentages of the height.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LineRange {
+    start: Option<Position>,
+    end: Option<Position>,
+}
+
+impl LineRange {
+    fn resolve(&self, height: u32) -> Range<u32> {
+        let start = self.start.map_or(0, |start| start.resolve(height));
+        let end = self.end.map_or(height, |end| end.resolve(height));
+        start..end.max(start)
+    }
+}
+
+impl FromStr for LineRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once("..")
+            .ok_or(format!("`{}` is not a range such as `10..200` or `25%..75%`", s))?;
+        let parse = |position: &str| match position.trim() {
+            "" => Ok(None),
+            position => position.parse().map(Some),
+        };
+        Ok(LineRange {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+}
+
+/// Position is a scan line given as its line number or a percentage of the height.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Position {
+    Line(u32),
+    Percent(f64),
+}
+
+impl Position {
+    pub fn resolve(&self, height: u32) -> u32 {
+        match *self {
+            Position::Line(line) => line.min(height),
+            Position::Percent(percent) => (height as f64 * percent.clamp(0.0, 100.0) / 100.0).round() as u32,
+        }
+    }
+}
+
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let position = match s.strip_suffix('%') {
+            Some(percent) => percent.trim().parse().ok().map(Position::Percent),
+            None => s.parse().ok().map(Position::Line),
+        };
+        position.ok_or(format!("`{}` is neither a line number nor a percentage", s))
+    }
+}
+
+pub fn parse_region(s: &str) -> Result<Region, String> {
+    let values: Vec<u32> = s
+        .split(',')
+        .map(|value| value.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("`{}` is not a rectangle such as `10,20,100,50`", s))?;
+    match values[..] {
+        [x, y, width, height] => Ok(Region::new(x, y, width, height)),
+        _ => Err(format!("`{}` should have 4 values: x, y, width, and height", s)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn target(lines: Option<&str>, every: Option<u32>, rect: Option<&str>) -> Target {
+        Target {
+            lines: lines.map(|lines| lines.parse().unwrap()),
+            every,
+            rect: rect.map(|rect| parse_region(rect).unwrap()),
+            walk: None,
+        }
+    }
+
+    #[test]
+    fn test_scan_lines() {
+        assert_eq!(vec![0..100], target(None, None, None).scan_lines(100, 0));
+        assert_eq!(vec![10..100], target(Some("10..200"), None, None).scan_lines(100, 0));
+        assert_eq!(vec![0..30], target(Some("..30"), None, None).scan_lines(100, 0));
+        assert_eq!(vec![50..150], target(Some("25%..75%"), None, None).scan_lines(200, 0));
+        assert_eq!(vec![2..3, 5..6, 8..9], target(Some("2..10"), Some(3), None).scan_lines(100, 0));
+        assert_eq!(vec![20..40], target(Some("..40"), None, Some("5,20,10,50")).scan_lines(100, 0));
+        assert_eq!(Vec::<Range<u32>>::new(), target(Some("50..10"), None, None).scan_lines(100, 0));
+    }
+
+    #[test]
+    fn test_walk() {
+        let walking = Target {
+            walk: Some(30),
+            ..target(Some("10..20"), None, Some("0,10,5,10"))
+        };
+        assert_eq!(vec![10..20], walking.scan_lines(100, 0));
+        assert_eq!(vec![70..80], walking.scan_lines(100, 2));
+        assert_eq!(vec![70..75], walking.scan_lines(75, 2));
+        assert_eq!(Vec::<Range<u32>>::new(), walking.scan_lines(100, 3));
+        assert_eq!(Some(Mask::rect(5, 100, Region::new(0, 70, 5, 10))), walking.mask(5, 100, 2));
+
+        let backwards = Target {
+            walk: Some(-15),
+            ..target(Some("10..20"), None, None)
+        };
+        assert_eq!(vec![0..5], backwards.scan_lines(100, 1));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("10".parse::<LineRange>().is_err());
+        assert!("a..10".parse::<LineRange>().is_err());
+        assert!(parse_region("1,2,3").is_err());
+        assert!(parse_region("1,2,3,x").is_err());
+    }
+}